@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use crate::config::Settings;
+use crate::convert::{self, Exporter, Importer};
+use crate::error::{Error, Result};
+use crate::file;
+use crate::template::Template;
+
+/// What a batch conversion does with each file.
+#[derive(Clone, Copy)]
+pub enum BatchMode<'a> {
+    /// Converts legacy files into TASD files, detecting each file's format unless an importer is given. `console_type`
+    /// is only used for formats which don't record one, and `template` is applied to every new file.
+    Import { importer: Option<&'static dyn Importer>, console_type: Option<u8>, template: Option<&'a Template> },
+    /// Converts TASD files into legacy files, choosing the format from each file's CONSOLE_TYPE unless one is given.
+    Export { exporter: Option<&'static dyn Exporter>, console_type: Option<u8> },
+}
+
+/// Outcome of converting a single file.
+pub struct BatchResult {
+    pub input: PathBuf,
+    /// The file which was written, or why the conversion failed.
+    pub output: Result<PathBuf>,
+}
+
+/// Expands files, directories, and glob patterns (such as `movies/*.r08`) into the list of files to convert.
+///
+/// Directories contain every file which the mode can convert: files with a legacy extension when importing, and .tasd
+/// files when exporting. Files named directly, or matched by a pattern, are always included.
+pub fn collect_inputs(patterns: &[&str], mode: &BatchMode) -> Result<Vec<PathBuf>> {
+    let mut out = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        if path.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(path)?.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && accepts(mode, path))
+                .collect();
+            files.sort();
+            out.extend(files);
+        } else if path.is_file() {
+            out.push(path.to_owned());
+        } else {
+            let matches = glob::glob(pattern).map_err(|err| Error::Parse(format!("{}: {}", pattern, err)))?;
+            let count = out.len();
+            out.extend(matches.filter_map(|path| path.ok()).filter(|path| path.is_file()));
+            if out.len() == count { return Err(format!("No files found matching: {}", pattern).into()) }
+        }
+    }
+    
+    let mut seen = HashSet::new();
+    out.retain(|path| seen.insert(path.clone()));
+    
+    Ok(out)
+}
+
+/// Converts every input using up to `jobs` threads. A failure is recorded in the results and doesn't stop the other
+/// conversions. Results are in the same order as `inputs`.
+///
+/// Outputs are written next to their inputs, or into `out_dir` if given.
+pub fn run(inputs: &[PathBuf], mode: BatchMode, out_dir: Option<&Path>, jobs: usize, settings: &Settings) -> Vec<BatchResult> {
+    let next = AtomicUsize::new(0);
+    let claimed = Mutex::new(HashSet::new());
+    let results = Mutex::new(Vec::from_iter(std::iter::repeat_with(|| None).take(inputs.len())));
+    
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= inputs.len() { break }
+                
+                let output = convert(&inputs[i], mode, out_dir, &claimed, settings);
+                results.lock().unwrap()[i] = Some(BatchResult { input: inputs[i].clone(), output });
+            });
+        }
+    });
+    
+    results.into_inner().unwrap().into_iter().flatten().collect()
+}
+
+/// Summary of a batch conversion: totals, then every failure with its reason, then every file written.
+pub fn report(results: &[BatchResult]) -> String {
+    let failed: Vec<&BatchResult> = results.iter().filter(|result| result.output.is_err()).collect();
+    let mut out = format!("Converted {} of {} files, {} failed.\n", results.len() - failed.len(), results.len(), failed.len());
+    
+    if !failed.is_empty() {
+        out.push_str("\nFailed:\n");
+        for result in &failed {
+            out.push_str(&format!("  {}: {}\n", result.input.to_string_lossy(), result.output.as_ref().unwrap_err()));
+        }
+    }
+    if failed.len() < results.len() {
+        out.push_str("\nConverted:\n");
+        for result in results {
+            if let Ok(output) = &result.output {
+                out.push_str(&format!("  {} -> {}\n", result.input.to_string_lossy(), output.to_string_lossy()));
+            }
+        }
+    }
+    
+    out
+}
+
+fn accepts(mode: &BatchMode, path: &Path) -> bool {
+    match mode {
+        BatchMode::Import { importer: Some(importer), .. } => path.extension().is_some_and(|extension| importer.metadata().extensions.iter().any(|candidate| extension.to_string_lossy().eq_ignore_ascii_case(candidate))),
+        BatchMode::Import { importer: None, .. } => convert::importer_for_extension(path).is_some(),
+        BatchMode::Export { .. } => path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("tasd")),
+    }
+}
+
+fn convert(input: &Path, mode: BatchMode, out_dir: Option<&Path>, claimed: &Mutex<HashSet<PathBuf>>, settings: &Settings) -> Result<PathBuf> {
+    match mode {
+        BatchMode::Import { importer, console_type, template } => {
+            let data = std::fs::read(input)?;
+            let importer = convert::resolve_importer(input, &data, importer)?;
+            let console_type = convert::import_console_type(importer, console_type.filter(|_| importer.console_types().len() > 1))?;
+            
+            let output = place(input.with_extension("tasd"), out_dir);
+            claim(&output, claimed)?;
+            convert::import_file(input, &data, &output, importer, console_type, template, settings)?;
+            
+            Ok(output)
+        },
+        BatchMode::Export { exporter, console_type } => {
+            let tasd = file::load(input)?;
+            let (exporter, console_type) = convert::export_target(&tasd, exporter, console_type)?;
+            let output = place(convert::export_path(input, exporter, settings), out_dir);
+            claim(&output, claimed)?;
+            convert::write_output(&output, &exporter.export(&tasd, console_type)?)?;
+            
+            Ok(output)
+        },
+    }
+}
+
+/// Moves a path into `out_dir`, keeping its file name.
+fn place(path: PathBuf, out_dir: Option<&Path>) -> PathBuf {
+    match out_dir {
+        Some(dir) => dir.join(path.file_name().unwrap_or_default()),
+        None => path,
+    }
+}
+
+/// Makes sure no two inputs are written to the same file.
+fn claim(output: &Path, claimed: &Mutex<HashSet<PathBuf>>) -> Result<()> {
+    if !claimed.lock().unwrap().insert(output.to_owned()) {
+        return Err(format!("Another input is also converted to {}", output.to_string_lossy()).into());
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::raw::Header;
+use crate::style::ColorMode;
+
+/// User preferences, read from `config.toml` in the user's config directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Directory legacy files are exported to, instead of next to the TASD file.
+    pub export_dir: Option<PathBuf>,
+    pub color: Option<ColorMode>,
+    /// Whether edits made from the menus are saved right away.
+    pub auto_save: bool,
+    /// Added to NES imports which don't have a NES_LATCH_FILTER yet, and offered when creating one.
+    pub nes_latch_filter: Option<u16>,
+    /// Added to NES imports which don't have a NES_CLOCK_FILTER yet, and offered when creating one.
+    pub nes_clock_filter: Option<u8>,
+    /// Offered as the name when creating an ATTRIBUTION packet.
+    pub attribution: Option<String>,
+}
+impl Config {
+    pub const DEFAULT: Config = Config {
+        export_dir: None,
+        color: None,
+        auto_save: true,
+        nes_latch_filter: None,
+        nes_clock_filter: None,
+        attribution: None,
+    };
+    
+    /// Location of the config file: `$XDG_CONFIG_HOME/tasd-edit/config.toml`, falling back to `~/.config` (or
+    /// `%APPDATA%` on Windows).
+    pub fn path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+            .or_else(|| if cfg!(windows) { std::env::var_os("APPDATA").map(PathBuf::from) } else { None })
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        
+        Some(dir.join("tasd-edit").join("config.toml"))
+    }
+    
+    /// Reads the config file, or the defaults if there isn't one.
+    pub fn load() -> Result<Self> {
+        match Self::path() {
+            Some(path) if path.is_file() => Self::from_toml(&std::fs::read_to_string(&path)?).map_err(|err| Error::Other(format!("{}: {}", path.to_string_lossy(), err))),
+            _ => Ok(Self::DEFAULT),
+        }
+    }
+    
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or("Unable to find a config directory. Set XDG_CONFIG_HOME or HOME.")?;
+        if let Some(parent) = path.parent() { std::fs::create_dir_all(parent)?; }
+        std::fs::write(path, self.to_toml())?;
+        
+        Ok(())
+    }
+    
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let document: toml::Value = text.parse().map_err(|err: toml::de::Error| Error::Parse(err.to_string()))?;
+        let table = document.as_table().unwrap();
+        let mut config = Self::DEFAULT;
+        for (name, value) in table {
+            let invalid = || Error::Parse(format!("Invalid value for '{}': {}", name, value));
+            match name.as_str() {
+                "export_dir" => config.export_dir = Some(PathBuf::from(value.as_str().ok_or_else(invalid)?)),
+                "color" => config.color = Some(value.as_str().ok_or_else(invalid)?.parse().map_err(|_| invalid())?),
+                "auto_save" => config.auto_save = value.as_bool().ok_or_else(invalid)?,
+                "nes_latch_filter" => config.nes_latch_filter = Some(value.as_integer().and_then(|value| value.try_into().ok()).ok_or_else(invalid)?),
+                "nes_clock_filter" => config.nes_clock_filter = Some(value.as_integer().and_then(|value| value.try_into().ok()).ok_or_else(invalid)?),
+                "attribution" => config.attribution = Some(value.as_str().ok_or_else(invalid)?.to_owned()),
+                _ => return Err(Error::Parse(format!("Unknown setting: {}", name))),
+            }
+        }
+        
+        Ok(config)
+    }
+    
+    /// Writes every setting, leaving unset ones as comments so the file shows what can be changed.
+    pub fn to_toml(&self) -> String {
+        let string = |value: &str| toml::Value::String(value.to_owned()).to_string();
+        let line = |name: &str, value: Option<String>, example: &str| match value {
+            Some(value) => format!("{} = {}\n", name, value),
+            None => format!("#{} = {}\n", name, example),
+        };
+        
+        let mut out = String::new();
+        out.push_str(&line("export_dir", self.export_dir.as_ref().map(|dir| string(&dir.to_string_lossy())), "\"exports\""));
+        out.push_str(&line("color", self.color.map(|color| string(color.name())), "\"auto\" # or \"always\", \"never\""));
+        out.push_str(&format!("auto_save = {}\n", self.auto_save));
+        out.push_str(&line("nes_latch_filter", self.nes_latch_filter.map(|time| time.to_string()), "80 # multiplied by 1.0us"));
+        out.push_str(&line("nes_clock_filter", self.nes_clock_filter.map(|time| time.to_string()), "16 # multiplied by 0.1us"));
+        out.push_str(&line("attribution", self.attribution.as_deref().map(string), "\"Your Name\""));
+        
+        out
+    }
+    
+    /// Packets configured to be added to imports for the console type, apart from any `tasd` already has.
+    pub fn import_defaults(&self, tasd: &TasdMovie, console_type: u8) -> Vec<Box<dyn Packet>> {
+        let mut out: Vec<Box<dyn Packet>> = Vec::new();
+        if console_type == 0x01 {
+            if let Some(time) = self.nes_latch_filter { out.push(Box::new(NesLatchFilter::new(time))) }
+            if let Some(time) = self.nes_clock_filter { out.push(Box::new(NesClockFilter::new(time))) }
+        }
+        out.retain(|packet| !tasd.packets.iter().any(|existing| existing.key() == packet.key()));
+        
+        out
+    }
+}
+
+/// What the library needs to know about the current run, passed to anything which imports, exports, creates, or saves
+/// files: the user's config, how files are saved, and which files have already been backed up.
+pub struct Settings {
+    pub config: Config,
+    /// Whether saving inserts or updates the DUMP_LAST_MODIFIED packet. Disabled for reproducible output.
+    pub update_last_modified: bool,
+    /// Version and key width given to new files.
+    pub new_file_header: Header,
+    /// Files which have already been backed up. Only the first save of each file makes a backup, so auto-saving every
+    /// edit can't push the version from before the session out of the backups.
+    backed_up: Mutex<Vec<PathBuf>>,
+}
+impl Settings {
+    pub const fn new(config: Config) -> Self {
+        Self {
+            config,
+            update_last_modified: true,
+            new_file_header: Header::LATEST,
+            backed_up: Mutex::new(Vec::new()),
+        }
+    }
+    
+    pub(crate) fn is_backed_up(&self, path: &Path) -> bool {
+        self.backed_up.lock().unwrap().iter().any(|backed_up| backed_up == path)
+    }
+    
+    pub(crate) fn set_backed_up(&self, path: &Path) {
+        let mut backed_up = self.backed_up.lock().unwrap();
+        if !backed_up.iter().any(|backed_up| backed_up == path) { backed_up.push(path.to_owned()); }
+    }
+}
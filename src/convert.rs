@@ -0,0 +1,361 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tasd::lookup::console_type_lut;
+use tasd::spec::*;
+use crate::config::Settings;
+use crate::error::Result;
+use crate::file;
+use crate::input;
+use crate::legacy;
+use crate::provenance;
+use crate::raw::packet_payload;
+use crate::template::Template;
+
+/// INPUT_MOMENT kind whose index is a frame number.
+const MOMENT_FRAME: u8 = 0x01;
+
+/// Describes a file format which can be imported or exported.
+pub struct FormatInfo {
+    /// Short unique name, used to pick the format on the command line.
+    pub name: &'static str,
+    /// Name shown in menus.
+    pub description: &'static str,
+    /// Lowercase file extensions, without the leading dot. The first is used when exporting.
+    pub extensions: &'static [&'static str],
+}
+
+/// A format which can be converted into packets.
+pub trait Importer: Sync {
+    fn metadata(&self) -> &'static FormatInfo;
+    
+    /// Console types the format can hold. If there's more than one, the file doesn't say which it's for and the user
+    /// needs to pick.
+    fn console_types(&self) -> &'static [u8];
+    
+    /// Whether the file looks like it's in this format. By default only the extension is checked.
+    fn detect(&self, path: &Path, _data: &[u8]) -> bool {
+        has_extension(path, self.metadata().extensions)
+    }
+    
+    /// Converts the contents of a file for the given console type into packets.
+    fn import(&self, path: &Path, data: &[u8], console_type: u8) -> Result<Vec<Box<dyn Packet>>>;
+}
+
+/// A format which packets can be converted into.
+pub trait Exporter: Sync {
+    fn metadata(&self) -> &'static FormatInfo;
+    
+    /// Console types which can be exported to this format.
+    fn console_types(&self) -> &'static [u8];
+    
+    /// Converts the inputs of `tasd` for the given console type into the contents of a file.
+    fn export(&self, tasd: &TasdMovie, console_type: u8) -> Result<Vec<u8>>;
+}
+
+/// Every supported import format. New formats only need to be added here to show up in the menus and command line.
+pub static IMPORTERS: &[&dyn Importer] = &[&legacy::R08, &legacy::R16m, &legacy::Gbi];
+
+/// Every supported export format. New formats only need to be added here to show up in the menus and command line.
+pub static EXPORTERS: &[&dyn Exporter] = &[&legacy::R08, &legacy::R16m, &legacy::Gbi];
+
+pub fn importer_by_name(name: &str) -> Option<&'static dyn Importer> {
+    IMPORTERS.iter().copied().find(|importer| importer.metadata().name.eq_ignore_ascii_case(name))
+}
+
+pub fn exporter_by_name(name: &str) -> Option<&'static dyn Exporter> {
+    EXPORTERS.iter().copied().find(|exporter| exporter.metadata().name.eq_ignore_ascii_case(name))
+}
+
+/// The first importer which recognizes the file.
+pub fn detect_importer(path: &Path, data: &[u8]) -> Option<&'static dyn Importer> {
+    IMPORTERS.iter().copied().find(|importer| importer.detect(path, data))
+}
+
+/// The first importer which handles the file's extension, for when its contents aren't available.
+pub fn importer_for_extension(path: &Path) -> Option<&'static dyn Importer> {
+    IMPORTERS.iter().copied().find(|importer| has_extension(path, importer.metadata().extensions))
+}
+
+/// The first exporter which supports the console type.
+pub fn exporter_for_console(console_type: u8) -> Option<&'static dyn Exporter> {
+    EXPORTERS.iter().copied().find(|exporter| exporter.console_types().contains(&console_type))
+}
+
+/// Every extension which can be imported, formatted for messages like `.r08, .r16m, .txt`.
+pub fn import_extensions() -> String {
+    IMPORTERS.iter().flat_map(|importer| importer.metadata().extensions).map(|extension| format!(".{}", extension)).collect::<Vec<_>>().join(", ")
+}
+
+/// Converts a file and appends the result to `tasd`, preceded by a comment which records where it came from, and
+/// followed by the packets the user's config adds to imports for the console type.
+pub fn import_into(tasd: &mut TasdMovie, importer: &dyn Importer, path: &Path, data: &[u8], console_type: u8, settings: &Settings) -> Result<()> {
+    let packets = importer.import(path, data, console_type)?;
+    tasd.packets.push(provenance::import_comment(path, data));
+    tasd.packets.extend(packets);
+    let defaults = settings.config.import_defaults(tasd, console_type);
+    tasd.packets.extend(defaults);
+    
+    Ok(())
+}
+
+/// How to settle a CONSOLE_TYPE or PORT_CONTROLLER packet of an appended file which disagrees with the movie's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    KeepExisting,
+    UseImported,
+    /// Stops the import, leaving the movie unchanged.
+    Cancel,
+}
+
+/// Converts a file and appends it to a movie which may already have inputs, continuing them rather than starting over.
+///
+/// The imported CONSOLE_TYPE and PORT_CONTROLLER packets are only added if the movie doesn't have them yet. When the
+/// movie's are different, `resolve` is given the existing and imported packets and decides which to keep. Imported
+/// INPUT_CHUNK data is added to the end of each port's last chunk, after padding the ports with idle frames so they
+/// line up. INPUT_MOMENT indexes are moved past the movie's last moment of the same port and kind, and frame-indexed
+/// moments also past the movie's existing frames, so they line up with the appended chunks.
+pub fn append_into(tasd: &mut TasdMovie, importer: &dyn Importer, path: &Path, data: &[u8], console_type: u8, settings: &Settings, mut resolve: impl FnMut(&dyn Packet, &dyn Packet) -> ConflictResolution) -> Result<()> {
+    let packets = importer.import(path, data, console_type)?;
+    
+    // Every conflict is settled before anything is changed, so cancelling leaves the movie as it was
+    let mut replaced = Vec::new();
+    let mut added = Vec::new();
+    let mut inputs = Vec::new();
+    for packet in packets {
+        if packet.key() == KEY_INPUT_CHUNK || packet.key() == KEY_INPUT_MOMENT { inputs.push(packet); continue }
+        
+        match existing_header(tasd, packet.as_ref()) {
+            Some(i) if packet_payload(tasd.packets[i].as_ref()) == packet_payload(packet.as_ref()) => (),
+            Some(i) => match resolve(tasd.packets[i].as_ref(), packet.as_ref()) {
+                ConflictResolution::KeepExisting => (),
+                ConflictResolution::UseImported => replaced.push((i, packet)),
+                ConflictResolution::Cancel => return Err("Import cancelled.".into()),
+            },
+            None => added.push(packet),
+        }
+    }
+    
+    for (i, packet) in replaced { tasd.packets[i] = packet; }
+    tasd.packets.push(provenance::import_comment(path, data));
+    tasd.packets.extend(added);
+    let first_frame = frame_count(tasd);
+    append_chunks(tasd, inputs.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>()).collect());
+    append_moments(tasd, inputs.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputMoment>()).collect(), first_frame);
+    let defaults = settings.config.import_defaults(tasd, console_type);
+    tasd.packets.extend(defaults);
+    
+    Ok(())
+}
+
+/// Console types of the file which can be exported, in the order they appear.
+pub fn export_console_types(tasd: &TasdMovie) -> Vec<u8> {
+    let mut out = Vec::new();
+    for packet in tasd.search_by_key(vec![KEY_CONSOLE_TYPE]) {
+        if let Some(packet) = packet.as_any().downcast_ref::<ConsoleType>() {
+            if exporter_for_console(packet.kind).is_some() && !out.contains(&packet.kind) { out.push(packet.kind) }
+        }
+    }
+    
+    out
+}
+
+/// The console type to import a file as: the importer's only console type, or `requested` if it supports more than one.
+pub fn import_console_type(importer: &dyn Importer, requested: Option<u8>) -> Result<u8> {
+    let console_types = importer.console_types();
+    match requested {
+        Some(console_type) if !console_types.contains(&console_type) => Err(format!("{} files can't be used for console type {:#04X}.", importer.metadata().description, console_type).into()),
+        Some(console_type) => Ok(console_type),
+        None if console_types.len() == 1 => Ok(console_types[0]),
+        None => Err(format!("{} files don't record which console they're for, use --console to choose one ({}).", importer.metadata().description, console_names(console_types)).into()),
+    }
+}
+
+/// The exporter and console type to export a file with. Unless requested, they're chosen from the file's CONSOLE_TYPE
+/// packets, which must only name one exportable console type.
+pub fn export_target(tasd: &TasdMovie, exporter: Option<&'static dyn Exporter>, requested: Option<u8>) -> Result<(&'static dyn Exporter, u8)> {
+    let mut console_types = export_console_types(tasd);
+    if let Some(exporter) = exporter {
+        console_types.retain(|kind| exporter.console_types().contains(kind));
+        if console_types.is_empty() && exporter.console_types().len() == 1 { console_types = exporter.console_types().to_vec() }
+    }
+    let console_type = match requested {
+        Some(console_type) => console_type,
+        None if console_types.len() == 1 => console_types[0],
+        None if console_types.is_empty() => return Err("Unable to determine what console this data is intended for. Please add a ConsoleType packet, or use --console.".into()),
+        None => return Err(format!("Multiple console types detected, use --console to choose one ({}).", console_names(&console_types)).into()),
+    };
+    
+    match exporter {
+        Some(exporter) if !exporter.console_types().contains(&console_type) => Err(format!("{} files can't be used for console type {:#04X}.", exporter.metadata().description, console_type).into()),
+        Some(exporter) => Ok((exporter, console_type)),
+        None => Ok((exporter_for_console(console_type).ok_or_else(|| format!("No legacy format supports console type {:#04X}.", console_type))?, console_type)),
+    }
+}
+
+/// The importer to use for a file: `importer` if given, otherwise the first one which recognizes the file.
+pub fn resolve_importer(path: &Path, data: &[u8], importer: Option<&'static dyn Importer>) -> Result<&'static dyn Importer> {
+    match importer {
+        Some(importer) => Ok(importer),
+        None => Ok(detect_importer(path, data).ok_or_else(|| format!("Unable to identify file, use --format to choose one ({}).", import_extensions()))?),
+    }
+}
+
+/// Converts the contents of a legacy file into a new TASD file at `output`, after applying `template` to it.
+pub fn import_file(input: &Path, data: &[u8], output: &Path, importer: &dyn Importer, console_type: u8, template: Option<&Template>, settings: &Settings) -> Result<()> {
+    let mut tasd = provenance::new_movie(output.to_owned(), settings.new_file_header);
+    import_into(&mut tasd, importer, input, data, console_type, settings)?;
+    if let Some(template) = template { template.apply(&mut tasd); }
+    file::save(&mut tasd, settings)
+}
+
+/// Converts the inputs of a TASD file into a legacy file, written to `output` or the `export_path`. Returns the path
+/// written to.
+pub fn export_file(input: &Path, output: Option<&Path>, exporter: Option<&'static dyn Exporter>, console_type: Option<u8>, settings: &Settings) -> Result<PathBuf> {
+    let tasd = file::load(input)?;
+    let (exporter, console_type) = export_target(&tasd, exporter, console_type)?;
+    let output = output.map(Path::to_owned).unwrap_or_else(|| export_path(input, exporter, settings));
+    write_output(&output, &exporter.export(&tasd, console_type)?)?;
+    
+    Ok(output)
+}
+
+/// Where a file is exported to by default: its own path with an `.export.<format>` extension, placed in the configured
+/// export directory if there is one.
+pub fn export_path(input: &Path, exporter: &dyn Exporter, settings: &Settings) -> PathBuf {
+    let path = input.with_extension(format!("export.{}", exporter.metadata().extensions[0]));
+    match &settings.config.export_dir {
+        Some(dir) => dir.join(path.file_name().unwrap_or_default()),
+        None => path,
+    }
+}
+
+/// Writes converted data, creating the directory it goes in if needed.
+pub fn write_output(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) { std::fs::create_dir_all(parent)?; }
+    std::fs::write(path, data)?;
+    
+    Ok(())
+}
+
+/// Index of the movie's CONSOLE_TYPE packet, or of its PORT_CONTROLLER packet for the same port.
+fn existing_header(tasd: &TasdMovie, packet: &dyn Packet) -> Option<usize> {
+    let port = packet.as_any().downcast_ref::<PortController>().map(|controller| controller.port);
+    tasd.packets.iter().position(|existing| match port {
+        Some(port) => existing.as_any().downcast_ref::<PortController>().is_some_and(|existing| existing.port == port),
+        None => packet.key() == KEY_CONSOLE_TYPE && existing.key() == KEY_CONSOLE_TYPE,
+    })
+}
+
+fn append_chunks(tasd: &mut TasdMovie, chunks: Vec<&InputChunk>) {
+    let controllers = input::port_controllers(&tasd.packets);
+    let frame_size = |port: u8| controllers.get(&port).map(|kind| input::frame_size(*kind)).unwrap_or(1);
+    let frames = chunks.iter().map(|chunk| input::port_inputs(tasd, chunk.port).len().div_ceil(frame_size(chunk.port))).max().unwrap_or(0);
+    
+    for chunk in chunks {
+        let padding = (frames * frame_size(chunk.port)).saturating_sub(input::port_inputs(tasd, chunk.port).len());
+        let last = tasd.packets.iter().rposition(|packet| packet.as_any().downcast_ref::<InputChunk>().is_some_and(|existing| existing.port == chunk.port));
+        let mut inputs = last.and_then(|i| tasd.packets[i].as_any().downcast_ref::<InputChunk>()).map(|existing| existing.inputs.clone()).unwrap_or_default();
+        inputs.extend(std::iter::repeat_n(0xFF, padding));
+        inputs.extend_from_slice(&chunk.inputs);
+        
+        let merged = Box::new(InputChunk::new(chunk.port, inputs));
+        match last {
+            Some(i) => tasd.packets[i] = merged,
+            None => tasd.packets.push(merged),
+        }
+    }
+}
+
+/// Number of frames on the movie's longest port.
+fn frame_count(tasd: &TasdMovie) -> usize {
+    let controllers = input::port_controllers(&tasd.packets);
+    let ports: HashSet<u8> = tasd.packets.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>()).map(|chunk| chunk.port).collect();
+    ports.into_iter().map(|port| input::port_inputs(tasd, port).len().div_ceil(controllers.get(&port).map(|kind| input::frame_size(*kind)).unwrap_or(1))).max().unwrap_or(0)
+}
+
+fn append_moments(tasd: &mut TasdMovie, moments: Vec<&InputMoment>, first_frame: usize) {
+    let mut offsets = HashMap::new();
+    for moment in moments {
+        let offset = *offsets.entry((moment.port, moment.kind)).or_insert_with(|| {
+            let next = tasd.packets.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputMoment>())
+                .filter(|existing| existing.port == moment.port && existing.kind == moment.kind)
+                .map(|existing| existing.index + 1).max().unwrap_or(0);
+            if moment.kind == MOMENT_FRAME { next.max(first_frame as u64) } else { next }
+        });
+        tasd.packets.push(Box::new(InputMoment::new(moment.port, moment.kind, moment.index + offset, moment.inputs.clone())));
+    }
+}
+
+fn console_names(console_types: &[u8]) -> String {
+    console_types.iter().map(|kind| console_type_lut(*kind).unwrap_or("Unknown")).collect::<Vec<_>>().join(", ")
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|extension| extensions.iter().any(|candidate| extension.to_string_lossy().eq_ignore_ascii_case(candidate)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::raw::Header;
+    
+    fn no_conflicts(_: &dyn Packet, _: &dyn Packet) -> ConflictResolution {
+        panic!("unexpected conflict")
+    }
+    
+    fn moments(tasd: &TasdMovie) -> Vec<(u8, u64)> {
+        tasd.packets.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputMoment>()).map(|moment| (moment.kind, moment.index)).collect()
+    }
+    
+    #[test]
+    fn appended_inputs_continue_existing_ones() {
+        let (first, second) = ([0x01, 0x02, 0x03, 0x04], [0x05, 0x06]);
+        let settings = Settings::new(Config::DEFAULT);
+        let mut tasd = provenance::new_movie(PathBuf::from("movie.tasd"), Header::LATEST);
+        import_into(&mut tasd, &legacy::R08, Path::new("first.r08"), &first, 0x01, &settings).unwrap();
+        append_into(&mut tasd, &legacy::R08, Path::new("second.r08"), &second, 0x01, &settings, no_conflicts).unwrap();
+        
+        assert_eq!(tasd.search_by_key(vec![KEY_INPUT_CHUNK]).len(), 2);
+        assert_eq!(tasd.search_by_key(vec![KEY_CONSOLE_TYPE]).len(), 1);
+        assert_eq!(legacy::R08.export(&tasd, 0x01).unwrap(), [first.as_slice(), second.as_slice()].concat());
+    }
+    
+    #[test]
+    fn cancelling_leaves_the_movie_unchanged() {
+        let settings = Settings::new(Config::DEFAULT);
+        let mut tasd = provenance::new_movie(PathBuf::from("movie.tasd"), Header::LATEST);
+        import_into(&mut tasd, &legacy::R08, Path::new("first.r08"), &[0x01, 0x02], 0x01, &settings).unwrap();
+        let before: Vec<Vec<u8>> = tasd.packets.iter().map(|packet| packet.raw()).collect();
+        
+        let result = append_into(&mut tasd, &legacy::R16m, Path::new("second.r16m"), &[0; 16], 0x02, &settings, |_, _| ConflictResolution::Cancel);
+        assert_eq!(result.unwrap_err().to_string(), "Import cancelled.");
+        assert_eq!(tasd.packets.iter().map(|packet| packet.raw()).collect::<Vec<_>>(), before);
+    }
+    
+    #[test]
+    fn appended_moments_follow_the_last_moment() {
+        let settings = Settings::new(Config::DEFAULT);
+        let mut tasd = provenance::new_movie(PathBuf::from("movie.tasd"), Header::LATEST);
+        import_into(&mut tasd, &legacy::Gbi, Path::new("first.txt"), b"00000010 0001\n00000020 0002\n", 0x05, &settings).unwrap();
+        append_into(&mut tasd, &legacy::Gbi, Path::new("second.txt"), b"00000000 0003\n00000010 0004\n", 0x05, &settings, no_conflicts).unwrap();
+        
+        assert_eq!(moments(&tasd), [(0x02, 0x10), (0x02, 0x20), (0x02, 0x21), (0x02, 0x31)]);
+    }
+    
+    #[test]
+    fn moments_are_offset_per_kind() {
+        let mut tasd = provenance::new_movie(PathBuf::from("movie.tasd"), Header::LATEST);
+        tasd.packets.push(Box::new(PortController::new(1, 0x0101)));
+        tasd.packets.push(Box::new(InputChunk::new(1, vec![0xFF; 3])));
+        tasd.packets.push(Box::new(InputMoment::new(1, 0x01, 1, vec![0xFE])));
+        tasd.packets.push(Box::new(InputMoment::new(1, 0x02, 500, vec![0xFE])));
+        
+        let first_frame = frame_count(&tasd);
+        let appended = [InputMoment::new(1, 0x01, 0, vec![0xFD]), InputMoment::new(1, 0x02, 0, vec![0xFD])];
+        append_moments(&mut tasd, appended.iter().collect(), first_frame);
+        
+        assert_eq!(first_frame, 3);
+        assert_eq!(moments(&tasd), [(0x01, 1), (0x02, 500), (0x01, 3), (0x02, 501)]);
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::BTreeSet;
+use crossterm::style::Color;
+use serde_json::{json, Value};
+use tasd::spec::*;
+use crate::fields::key_label;
+use crate::input::{frame_size, port_inputs};
+use crate::provenance;
+use crate::raw::packet_payload;
+use crate::style::{strip_ansi, Style};
+
+/// Keys which are compared frame-by-frame instead of as metadata.
+const INPUT_KEYS: [Key; 1] = [KEY_INPUT_CHUNK];
+
+/// Maximum number of frame ranges listed in the human-readable summary.
+const RANGE_SUMMARY_LIMIT: usize = 10;
+
+pub struct MovieDiff {
+    pub metadata: Vec<MetadataChange>,
+    pub ports: Vec<PortDiff>,
+}
+
+/// Packets of a single key which exist in only one of the two files, formatted with colors.
+pub struct MetadataChange {
+    pub key: Key,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+pub struct PortDiff {
+    pub port: u8,
+    pub frame_size: usize,
+    pub left_frames: usize,
+    pub right_frames: usize,
+    /// Inclusive ranges of differing frame indexes (0-indexed).
+    pub ranges: Vec<(usize, usize)>,
+}
+
+impl PortDiff {
+    pub fn differing(&self) -> usize {
+        self.ranges.iter().map(|(start, end)| end - start + 1).sum()
+    }
+    
+    pub fn first_divergence(&self) -> Option<usize> {
+        self.ranges.first().map(|range| range.0)
+    }
+}
+
+impl MovieDiff {
+    pub fn is_identical(&self) -> bool {
+        self.metadata.is_empty() && self.ports.iter().all(|port| port.ranges.is_empty())
+    }
+    
+    /// Human-readable report, one line per element.
+    pub fn pretty(&self, style: Style) -> Vec<String> {
+        let packet = |packet: &str| if style.color { packet.to_owned() } else { strip_ansi(packet) };
+        let mut out = Vec::new();
+        
+        if !self.metadata.is_empty() {
+            out.push("Metadata:".to_owned());
+            for change in &self.metadata {
+                change.removed.iter().for_each(|removed| out.push(format!("  {} {}", style.paint("-", Color::Red), packet(removed))));
+                change.added.iter().for_each(|added| out.push(format!("  {} {}", style.paint("+", Color::Green), packet(added))));
+            }
+        }
+        
+        for port in &self.ports {
+            match port.first_divergence() {
+                None => out.push(format!("Port #{}: {} frames, identical", port.port, port.left_frames)),
+                Some(first) => {
+                    out.push(format!("Port #{}: {} of {} frames differ, first divergence at frame {}",
+                        port.port, port.differing(), port.left_frames.max(port.right_frames), style.paint(first, Color::Yellow)));
+                    if port.left_frames != port.right_frames {
+                        out.push(format!("  Length differs: {} vs {} frames", port.left_frames, port.right_frames));
+                    }
+                    out.push(format!("  Differing frames: {}", summarize_ranges(&port.ranges, RANGE_SUMMARY_LIMIT)));
+                },
+            }
+        }
+        
+        if self.is_identical() {
+            out.push("Files are identical.".to_owned());
+        }
+        
+        out
+    }
+    
+    /// Machine-readable report.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "identical": self.is_identical(),
+            "metadata": self.metadata.iter().map(|change| json!({
+                "key": key_label(&change.key),
+                "removed": change.removed.iter().map(|s| strip_ansi(s)).collect::<Vec<String>>(),
+                "added": change.added.iter().map(|s| strip_ansi(s)).collect::<Vec<String>>(),
+            })).collect::<Vec<Value>>(),
+            "ports": self.ports.iter().map(|port| json!({
+                "port": port.port,
+                "frame_size": port.frame_size,
+                "left_frames": port.left_frames,
+                "right_frames": port.right_frames,
+                "differing_frames": port.differing(),
+                "first_divergence": port.first_divergence(),
+                "ranges": port.ranges.iter().map(|(start, end)| json!([start, end])).collect::<Vec<Value>>(),
+            })).collect::<Vec<Value>>(),
+        })
+    }
+}
+
+/// Compares the metadata packets (by key and content) and the per-port input data of two files.
+///
+/// Unless `include_volatile` is set, packets which only describe when and how each file was made (see `is_volatile`)
+/// are left out, so that two dumps of the same run compare as identical.
+pub fn diff_movies(left: &TasdMovie, right: &TasdMovie, include_volatile: bool) -> MovieDiff {
+    let compared = |packet: &dyn Packet| include_volatile || !is_volatile(packet);
+    let mut keys = BTreeSet::new();
+    left.packets.iter().chain(right.packets.iter())
+        .filter(|packet| !INPUT_KEYS.contains(&packet.key()) && compared(packet.as_ref()))
+        .for_each(|packet| { keys.insert(packet.key()); });
+    
+    let mut metadata = Vec::new();
+    for key in keys {
+        let mut left_packets: Vec<&Box<dyn Packet>> = left.search_by_key(vec![key]).into_iter().filter(|packet| compared(packet.as_ref())).collect();
+        let mut right_packets: Vec<&Box<dyn Packet>> = right.search_by_key(vec![key]).into_iter().filter(|packet| compared(packet.as_ref())).collect();
+        
+        // remove every packet which has an exact match on the other side, leaving only the changes
+        let mut i = 0;
+        while i < left_packets.len() {
+            let payload = packet_payload(left_packets[i].as_ref());
+            if let Some(j) = right_packets.iter().position(|packet| packet_payload(packet.as_ref()) == payload) {
+                left_packets.remove(i);
+                right_packets.remove(j);
+            } else {
+                i += 1;
+            }
+        }
+        
+        if !left_packets.is_empty() || !right_packets.is_empty() {
+            metadata.push(MetadataChange {
+                key,
+                removed: left_packets.iter().map(|packet| Style::COLOR.packet(packet.as_ref())).collect(),
+                added: right_packets.iter().map(|packet| Style::COLOR.packet(packet.as_ref())).collect(),
+            });
+        }
+    }
+    
+    let mut ports = BTreeSet::new();
+    left.search_by_key(vec![KEY_INPUT_CHUNK]).iter().chain(right.search_by_key(vec![KEY_INPUT_CHUNK]).iter())
+        .filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>())
+        .for_each(|chunk| { ports.insert(chunk.port); });
+    
+    let ports = ports.into_iter().map(|port| {
+        let size = port_controller(left, port).or_else(|| port_controller(right, port)).map(frame_size).unwrap_or(1);
+        diff_port(port, size, &port_inputs(left, port), &port_inputs(right, port))
+    }).collect();
+    
+    MovieDiff { metadata, ports }
+}
+
+/// DUMP_CREATED, DUMP_LAST_MODIFIED, and the comments added by `provenance`, which differ between any two dumps.
+pub fn is_volatile(packet: &dyn Packet) -> bool {
+    matches!(packet.key(), KEY_DUMP_CREATED | KEY_DUMP_LAST_MODIFIED) || provenance::is_provenance_comment(packet)
+}
+
+fn diff_port(port: u8, frame_size: usize, left: &[u8], right: &[u8]) -> PortDiff {
+    let left_frames: Vec<&[u8]> = left.chunks(frame_size).collect();
+    let right_frames: Vec<&[u8]> = right.chunks(frame_size).collect();
+    
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in 0..left_frames.len().max(right_frames.len()) {
+        if left_frames.get(i) != right_frames.get(i) {
+            match ranges.last_mut() {
+                Some(range) if range.1 + 1 == i => range.1 = i,
+                _ => ranges.push((i, i)),
+            }
+        }
+    }
+    
+    PortDiff {
+        port,
+        frame_size,
+        left_frames: left_frames.len(),
+        right_frames: right_frames.len(),
+        ranges,
+    }
+}
+
+fn port_controller(tasd: &TasdMovie, port: u8) -> Option<u16> {
+    tasd.search_by_key(vec![KEY_PORT_CONTROLLER]).iter()
+        .filter_map(|packet| packet.as_any().downcast_ref::<PortController>())
+        .find(|controller| controller.port == port)
+        .map(|controller| controller.kind)
+}
+
+/// Formats ranges compactly, e.g. `12-40, 100, 2000-2010`.
+pub fn summarize_ranges(ranges: &[(usize, usize)], limit: usize) -> String {
+    let mut parts: Vec<String> = ranges.iter().take(limit).map(|(start, end)| {
+        if start == end { start.to_string() } else { format!("{}-{}", start, end) }
+    }).collect();
+    if ranges.len() > limit {
+        parts.push(format!("... ({} more)", ranges.len() - limit));
+    }
+    
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    
+    fn movie(packets: Vec<Box<dyn Packet>>) -> TasdMovie {
+        TasdMovie { version: 1, keylen: 2, packets, source_path: PathBuf::from("movie.tasd") }
+    }
+    
+    fn inputs(port_one: Vec<u8>, port_two: Vec<u8>) -> Vec<Box<dyn Packet>> {
+        vec![
+            Box::new(PortController::new(1, 0x0101)),
+            Box::new(PortController::new(2, 0x0201)),
+            Box::new(InputChunk::new(1, port_one)),
+            Box::new(InputChunk::new(2, port_two)),
+        ]
+    }
+    
+    #[test]
+    fn identical_files_have_no_changes() {
+        let left = movie(inputs(vec![0xFF; 4], vec![0xFF; 8]));
+        let right = movie(inputs(vec![0xFF; 4], vec![0xFF; 8]));
+        let diff = diff_movies(&left, &right, false);
+        assert!(diff.is_identical());
+        assert_eq!(diff.pretty(Style::PLAIN), ["Port #1: 4 frames, identical", "Port #2: 4 frames, identical", "Files are identical."]);
+    }
+    
+    #[test]
+    fn lists_metadata_changes() {
+        let mut left = movie(vec![Box::new(GameTitle::new("Mario".to_owned())), Box::new(Rerecords::new(5))]);
+        let right = movie(vec![Box::new(GameTitle::new("Luigi".to_owned())), Box::new(Rerecords::new(5)), Box::new(Comment::new("hi".to_owned()))]);
+        let diff = diff_movies(&left, &right, false);
+        assert_eq!(diff.metadata.iter().map(|change| (change.key, change.removed.len(), change.added.len())).collect::<Vec<_>>(), [(KEY_GAME_TITLE, 1, 1), (KEY_COMMENT, 0, 1)]);
+        assert!(diff.pretty(Style::PLAIN).iter().all(|line| strip_ansi(line) == *line));
+        
+        // packets are matched by content, regardless of their order
+        left.packets.reverse();
+        let diff = diff_movies(&left, &right, false);
+        assert!(diff.metadata.iter().all(|change| change.key != KEY_RERECORDS));
+    }
+    
+    #[test]
+    fn skips_volatile_packets_unless_asked() {
+        let left = movie(vec![
+            Box::new(DumpCreated::new(1_000)),
+            Box::new(DumpLastModified::new(2_000)),
+            provenance::import_comment(Path::new("a.r08"), &[0x01]),
+        ]);
+        let right = movie(vec![
+            Box::new(DumpCreated::new(3_000)),
+            provenance::import_comment(Path::new("b.r08"), &[0x02]),
+        ]);
+        assert!(diff_movies(&left, &right, false).is_identical());
+        
+        let diff = diff_movies(&left, &right, true);
+        assert_eq!(diff.metadata.iter().map(|change| change.key).collect::<Vec<Key>>(), [KEY_DUMP_CREATED, KEY_DUMP_LAST_MODIFIED, KEY_COMMENT]);
+    }
+    
+    #[test]
+    fn groups_differing_frames_into_ranges() {
+        let mut changed = vec![0xFF; 10];
+        changed[2] = 0x7F;
+        changed[3] = 0x7F;
+        changed[7] = 0x00;
+        let left = movie(inputs(vec![0xFF; 10], vec![0xFF; 8]));
+        // port 2 holds two bytes per frame, and is one frame longer on the right
+        let right = movie(inputs(changed, vec![0xFF; 10]));
+        let diff = diff_movies(&left, &right, false);
+        
+        let ports: Vec<_> = diff.ports.iter().map(|port| (port.port, port.left_frames, port.right_frames, port.ranges.clone())).collect();
+        assert_eq!(ports, [(1, 10, 10, vec![(2, 3), (7, 7)]), (2, 4, 5, vec![(4, 4)])]);
+        assert_eq!(diff.ports[0].differing(), 3);
+        assert_eq!(diff.ports[0].first_divergence(), Some(2));
+        assert_eq!(diff.pretty(Style::PLAIN)[0], "Port #1: 3 of 10 frames differ, first divergence at frame 2");
+        assert_eq!(diff.to_json()["ports"][1]["ranges"], json!([[4, 4]]));
+    }
+    
+    #[test]
+    fn summarizes_ranges_up_to_a_limit() {
+        assert_eq!(summarize_ranges(&[(12, 40), (100, 100), (2000, 2010)], 10), "12-40, 100, 2000-2010");
+        assert_eq!(summarize_ranges(&[(1, 1), (3, 3), (5, 5)], 2), "1, 3, ... (1 more)");
+        assert_eq!(summarize_ranges(&[], 10), "");
+    }
+}
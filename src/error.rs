@@ -0,0 +1,66 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::num::ParseIntError;
+use std::str::ParseBoolError;
+use tasd::spec::DumpError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Every error which can occur while editing, importing, or exporting a file.
+pub enum Error {
+    /// Errors reported by the `tasd` library.
+    Dump(DumpError),
+    Io(std::io::Error),
+    /// Input which couldn't be parsed, whether typed by the user or read from a file.
+    Parse(String),
+    Other(String),
+}
+
+impl Error {
+    /// Prefixes the error's message with where it happened, such as which packet or line, keeping parse errors as such.
+    pub fn context(self, context: impl Display) -> Self {
+        match self {
+            Error::Parse(msg) => Error::Parse(format!("{}: {}", context, msg)),
+            err => Error::Other(format!("{}: {}", context, err)),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // `DumpError`'s own Debug impl recurses forever for most variants, so each is formatted here instead
+            Error::Dump(DumpError::StdError(err)) => write!(f, "{}", err),
+            Error::Dump(DumpError::StdIo(kind)) => write!(f, "{:?}", kind),
+            Error::Dump(DumpError::InvalidMagic) => f.write_str("Magic Number doesn't match TASD format."),
+            Error::Dump(DumpError::Custom(msg)) => f.write_str(msg),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Parse(msg) => write!(f, "Invalid input: {}", msg),
+            Error::Other(msg) => f.write_str(msg),
+        }
+    }
+}
+impl Debug for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+impl std::error::Error for Error {}
+
+impl From<DumpError> for Error {
+    fn from(err: DumpError) -> Self { Error::Dump(err) }
+}
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self { Error::Io(err) }
+}
+impl From<ParseIntError> for Error {
+    fn from(err: ParseIntError) -> Self { Error::Parse(err.to_string()) }
+}
+impl From<ParseBoolError> for Error {
+    fn from(err: ParseBoolError) -> Self { Error::Parse(err.to_string()) }
+}
+impl From<String> for Error {
+    fn from(msg: String) -> Self { Error::Other(msg) }
+}
+impl From<&str> for Error {
+    fn from(msg: &str) -> Self { Error::Other(msg.to_owned()) }
+}
@@ -0,0 +1,271 @@
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::unknown::UnknownPacket;
+
+/// A generic, format-agnostic value of a single packet field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i128),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<u64>),
+    Packet(Box<PacketFields>),
+}
+
+/// A packet broken down into its named fields, in the order they appear in the payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PacketFields {
+    pub name: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+/// Name used for packets with keys that aren't part of the spec.
+pub const UNKNOWN_NAME: &str = "UNKNOWN";
+
+pub fn key_name(key: Key) -> Option<&'static str> {
+    get_keys().into_iter().find(|(k, _, _)| *k == key).map(|(_, name, _)| name)
+}
+
+/// Name of the packet type with this key, or `UNKNOWN 0xABCD` if the spec doesn't define it.
+pub fn key_label(key: &[u8]) -> String {
+    key.try_into().ok().and_then(key_name).map(str::to_owned).unwrap_or_else(|| format!("{} 0x{}", UNKNOWN_NAME, encode_hex(key)))
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    get_keys().into_iter().find(|(_, n, _)| n.eq_ignore_ascii_case(name)).map(|(key, _, _)| key)
+}
+
+macro_rules! downcast {
+    ($packet:expr, $kind:ty) => { $packet.as_any().downcast_ref::<$kind>().unwrap() };
+}
+
+fn int<T: Into<i128>>(value: T) -> Value { Value::Int(value.into()) }
+fn string(value: &str) -> Value { Value::Str(value.to_owned()) }
+fn nested(packet: &Option<Box<dyn Packet>>) -> Value {
+    match packet {
+        Some(packet) => Value::Packet(Box::new(to_fields(packet.as_ref()))),
+        None => Value::None,
+    }
+}
+
+/// Breaks a packet down into its fields.
+pub fn to_fields(packet: &dyn Packet) -> PacketFields {
+    let key = packet.key();
+    // packets which failed to parse, or have keys that aren't in the spec, only carry their key and payload
+    if let Some(unknown) = packet.as_any().downcast_ref::<UnknownPacket>() {
+        return PacketFields {
+            name: key_name(key).unwrap_or(UNKNOWN_NAME).to_owned(),
+            fields: vec![
+                ("key".to_owned(), Value::Bytes(unknown.key_bytes().to_vec())),
+                ("payload".to_owned(), Value::Bytes(unknown.payload().to_vec())),
+            ],
+        };
+    }
+    
+    let fields: Vec<(&str, Value)> = match key {
+        KEY_CONSOLE_TYPE => { let p = downcast!(packet, ConsoleType); vec![("kind", int(p.kind)), ("custom", p.custom.as_deref().map(string).unwrap_or(Value::None))] },
+        KEY_CONSOLE_REGION => vec![("region", int(downcast!(packet, ConsoleRegion).region))],
+        KEY_GAME_TITLE => vec![("title", string(&downcast!(packet, GameTitle).title))],
+        KEY_ROM_NAME => vec![("name", string(&downcast!(packet, RomName).name))],
+        KEY_ATTRIBUTION => { let p = downcast!(packet, Attribution); vec![("kind", int(p.kind)), ("name", string(&p.name))] },
+        KEY_CATEGORY => vec![("category", string(&downcast!(packet, Category).category))],
+        KEY_EMULATOR_NAME => vec![("name", string(&downcast!(packet, EmulatorName).name))],
+        KEY_EMULATOR_VERSION => vec![("version", string(&downcast!(packet, EmulatorVersion).version))],
+        KEY_EMULATOR_CORE => vec![("core", string(&downcast!(packet, EmulatorCore).core))],
+        KEY_TAS_LAST_MODIFIED => vec![("epoch", int(downcast!(packet, TasLastModified).epoch))],
+        KEY_DUMP_CREATED => vec![("epoch", int(downcast!(packet, DumpCreated).epoch))],
+        KEY_DUMP_LAST_MODIFIED => vec![("epoch", int(downcast!(packet, DumpLastModified).epoch))],
+        KEY_TOTAL_FRAMES => vec![("frames", int(downcast!(packet, TotalFrames).frames))],
+        KEY_RERECORDS => vec![("rerecords", int(downcast!(packet, Rerecords).rerecords))],
+        KEY_SOURCE_LINK => vec![("link", string(&downcast!(packet, SourceLink).link))],
+        KEY_BLANK_FRAMES => vec![("frames", int(downcast!(packet, BlankFrames).frames))],
+        KEY_VERIFIED => vec![("verified", Value::Bool(downcast!(packet, Verified).verified))],
+        KEY_MEMORY_INIT => { let p = downcast!(packet, MemoryInit); vec![
+            ("data_kind", int(p.data_kind)),
+            ("device_kind", int(p.device_kind)),
+            ("required", Value::Bool(p.required)),
+            ("name", string(&p.name)),
+            ("data", p.data.clone().map(Value::Bytes).unwrap_or(Value::None)),
+        ]},
+        KEY_GAME_IDENTIFIER => { let p = downcast!(packet, GameIdentifier); vec![("kind", int(p.kind)), ("encoding", int(p.encoding)), ("identifier", Value::Bytes(p.identifier.clone()))] },
+        KEY_MOVIE_LICENSE => vec![("license", string(&downcast!(packet, MovieLicense).license))],
+        KEY_MOVIE_FILE => { let p = downcast!(packet, MovieFile); vec![("name", string(&p.name)), ("data", Value::Bytes(p.data.clone()))] },
+        KEY_PORT_CONTROLLER => { let p = downcast!(packet, PortController); vec![("port", int(p.port)), ("kind", int(p.kind))] },
+        KEY_NES_LATCH_FILTER => vec![("time", int(downcast!(packet, NesLatchFilter).time))],
+        KEY_NES_CLOCK_FILTER => vec![("time", int(downcast!(packet, NesClockFilter).time))],
+        KEY_NES_OVERREAD => vec![("overread", Value::Bool(downcast!(packet, NesOverread).overread))],
+        KEY_NES_GAME_GENIE_CODE => vec![("code", string(&downcast!(packet, NesGameGenieCode).code))],
+        KEY_SNES_CLOCK_FILTER => vec![("time", int(downcast!(packet, SnesClockFilter).time))],
+        KEY_SNES_OVERREAD => vec![("overread", Value::Bool(downcast!(packet, SnesOverread).overread))],
+        KEY_SNES_GAME_GENIE_CODE => vec![("code", string(&downcast!(packet, SnesGameGenieCode).code))],
+        KEY_SNES_LATCH_TRAIN => vec![("trains", Value::List(downcast!(packet, SnesLatchTrain).trains.clone()))],
+        KEY_GENESIS_GAME_GENIE_CODE => vec![("code", string(&downcast!(packet, GenesisGameGenieCode).code))],
+        KEY_INPUT_CHUNK => { let p = downcast!(packet, InputChunk); vec![("port", int(p.port)), ("inputs", Value::Bytes(p.inputs.clone()))] },
+        KEY_INPUT_MOMENT => { let p = downcast!(packet, InputMoment); vec![
+            ("port", int(p.port)),
+            ("kind", int(p.kind)),
+            ("index", int(p.index)),
+            ("inputs", Value::Bytes(p.inputs.clone())),
+        ]},
+        KEY_TRANSITION => { let p = downcast!(packet, Transition); vec![
+            ("index_kind", int(p.index_kind)),
+            ("index", int(p.index)),
+            ("transition_kind", int(p.transition_kind)),
+            ("packet", nested(&p.packet)),
+        ]},
+        KEY_LAG_FRAME_CHUNK => { let p = downcast!(packet, LagFrameChunk); vec![("frame", int(p.frame)), ("count", int(p.count))] },
+        KEY_MOVIE_TRANSITION => { let p = downcast!(packet, MovieTransition); vec![
+            ("movie_frame", int(p.movie_frame)),
+            ("transition_kind", int(p.transition_kind)),
+            ("packet", nested(&p.packet)),
+        ]},
+        KEY_COMMENT => vec![("comment", string(&downcast!(packet, Comment).comment))],
+        KEY_EXPERIMENTAL => vec![("experimental", Value::Bool(downcast!(packet, Experimental).experimental))],
+        KEY_UNSPECIFIED => vec![("payload", Value::Bytes(downcast!(packet, Unspecified).payload.clone()))],
+        _ => vec![],
+    };
+    
+    PacketFields {
+        name: key_name(key).unwrap_or(UNKNOWN_NAME).to_owned(),
+        fields: fields.into_iter().map(|(name, value)| (name.to_owned(), value)).collect(),
+    }
+}
+
+/// Typed lookups into a field list, used when building packets.
+struct Reader<'a>(&'a PacketFields);
+impl<'a> Reader<'a> {
+    fn get(&self, name: &str) -> Option<&'a Value> {
+        self.0.fields.iter().find(|(n, _)| n == name).map(|(_, value)| value).filter(|value| **value != Value::None)
+    }
+    
+    fn require(&self, name: &str) -> Result<&'a Value> {
+        self.get(name).ok_or_else(|| Error::Parse(format!("{}: missing field '{}'", self.0.name, name)))
+    }
+    
+    fn int<T: TryFrom<i128>>(&self, name: &str) -> Result<T> {
+        match self.require(name)? {
+            Value::Int(value) => T::try_from(*value).map_err(|_| Error::Parse(format!("{}: '{}' is out of range", self.0.name, name))),
+            _ => Err(Error::Parse(format!("{}: '{}' must be an integer", self.0.name, name))),
+        }
+    }
+    
+    fn bool(&self, name: &str) -> Result<bool> {
+        match self.require(name)? {
+            Value::Bool(value) => Ok(*value),
+            _ => Err(Error::Parse(format!("{}: '{}' must be true or false", self.0.name, name))),
+        }
+    }
+    
+    fn string(&self, name: &str) -> Result<String> {
+        match self.require(name)? {
+            Value::Str(value) => Ok(value.clone()),
+            _ => Err(Error::Parse(format!("{}: '{}' must be a string", self.0.name, name))),
+        }
+    }
+    
+    fn opt_string(&self, name: &str) -> Result<Option<String>> {
+        self.get(name).map(|_| self.string(name)).transpose()
+    }
+    
+    /// Byte fields may also be given as strings of hexadecimal digits.
+    fn bytes(&self, name: &str) -> Result<Vec<u8>> {
+        match self.require(name)? {
+            Value::Bytes(value) => Ok(value.clone()),
+            Value::Str(value) => decode_hex(value).map_err(|err| err.context(format!("{}: '{}'", self.0.name, name))),
+            _ => Err(Error::Parse(format!("{}: '{}' must be hexadecimal bytes", self.0.name, name))),
+        }
+    }
+    
+    fn opt_bytes(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.get(name).map(|_| self.bytes(name)).transpose()
+    }
+    
+    fn list(&self, name: &str) -> Result<Vec<u64>> {
+        match self.require(name)? {
+            Value::List(value) => Ok(value.clone()),
+            _ => Err(Error::Parse(format!("{}: '{}' must be a list of whole numbers", self.0.name, name))),
+        }
+    }
+    
+    fn opt_packet(&self, name: &str) -> Result<Option<Box<dyn Packet>>> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Value::Packet(fields)) => from_fields(fields).map(Some),
+            Some(_) => Err(Error::Parse(format!("{}: '{}' must be a packet", self.0.name, name))),
+        }
+    }
+}
+
+/// Builds a packet from its fields. This is the inverse of `to_fields`.
+pub fn from_fields(fields: &PacketFields) -> Result<Box<dyn Packet>> {
+    let r = Reader(fields);
+    
+    if fields.name.eq_ignore_ascii_case(UNKNOWN_NAME) || r.get("key").is_some() {
+        let key = r.bytes("key")?;
+        let key: Key = key.try_into().map_err(|_| Error::Parse(format!("{}: 'key' must be exactly two bytes", fields.name)))?;
+        return Ok(Box::new(UnknownPacket::new(key, &r.bytes("payload")?)));
+    }
+    
+    let key = key_from_name(&fields.name).ok_or_else(|| Error::Parse(format!("Unknown packet type: {}", fields.name)))?;
+    let packet: Box<dyn Packet> = match key {
+        KEY_CONSOLE_TYPE => Box::new(ConsoleType::new(r.int("kind")?, r.opt_string("custom")?)),
+        KEY_CONSOLE_REGION => Box::new(ConsoleRegion::new(r.int("region")?)),
+        KEY_GAME_TITLE => Box::new(GameTitle::new(r.string("title")?)),
+        KEY_ROM_NAME => Box::new(RomName::new(r.string("name")?)),
+        KEY_ATTRIBUTION => Box::new(Attribution::new(r.int("kind")?, r.string("name")?)),
+        KEY_CATEGORY => Box::new(Category::new(r.string("category")?)),
+        KEY_EMULATOR_NAME => Box::new(EmulatorName::new(r.string("name")?)),
+        KEY_EMULATOR_VERSION => Box::new(EmulatorVersion::new(r.string("version")?)),
+        KEY_EMULATOR_CORE => Box::new(EmulatorCore::new(r.string("core")?)),
+        KEY_TAS_LAST_MODIFIED => Box::new(TasLastModified::new(r.int("epoch")?)),
+        KEY_DUMP_CREATED => Box::new(DumpCreated::new(r.int("epoch")?)),
+        KEY_DUMP_LAST_MODIFIED => Box::new(DumpLastModified::new(r.int("epoch")?)),
+        KEY_TOTAL_FRAMES => Box::new(TotalFrames::new(r.int("frames")?)),
+        KEY_RERECORDS => Box::new(Rerecords::new(r.int("rerecords")?)),
+        KEY_SOURCE_LINK => Box::new(SourceLink::new(r.string("link")?)),
+        KEY_BLANK_FRAMES => Box::new(BlankFrames::new(r.int("frames")?)),
+        KEY_VERIFIED => Box::new(Verified::new(r.bool("verified")?)),
+        KEY_MEMORY_INIT => Box::new(MemoryInit::new(r.int("data_kind")?, r.int("device_kind")?, r.bool("required")?, r.string("name")?, r.opt_bytes("data")?)),
+        KEY_GAME_IDENTIFIER => Box::new(GameIdentifier::new(r.int("kind")?, r.int("encoding")?, r.bytes("identifier")?)),
+        KEY_MOVIE_LICENSE => Box::new(MovieLicense::new(r.string("license")?)),
+        KEY_MOVIE_FILE => Box::new(MovieFile::new(r.string("name")?, r.bytes("data")?)),
+        KEY_PORT_CONTROLLER => Box::new(PortController::new(r.int("port")?, r.int("kind")?)),
+        KEY_NES_LATCH_FILTER => Box::new(NesLatchFilter::new(r.int("time")?)),
+        KEY_NES_CLOCK_FILTER => Box::new(NesClockFilter::new(r.int("time")?)),
+        KEY_NES_OVERREAD => Box::new(NesOverread::new(r.bool("overread")?)),
+        KEY_NES_GAME_GENIE_CODE => Box::new(NesGameGenieCode::new(r.string("code")?)),
+        KEY_SNES_CLOCK_FILTER => Box::new(SnesClockFilter::new(r.int("time")?)),
+        KEY_SNES_OVERREAD => Box::new(SnesOverread::new(r.bool("overread")?)),
+        KEY_SNES_GAME_GENIE_CODE => Box::new(SnesGameGenieCode::new(r.string("code")?)),
+        KEY_SNES_LATCH_TRAIN => Box::new(SnesLatchTrain::new(r.list("trains")?)),
+        KEY_GENESIS_GAME_GENIE_CODE => Box::new(GenesisGameGenieCode::new(r.string("code")?)),
+        KEY_INPUT_CHUNK => Box::new(InputChunk::new(r.int("port")?, r.bytes("inputs")?)),
+        KEY_INPUT_MOMENT => Box::new(InputMoment::new(r.int("port")?, r.int("kind")?, r.int("index")?, r.bytes("inputs")?)),
+        KEY_TRANSITION => Box::new(Transition::new(r.int("index_kind")?, r.int("index")?, r.int("transition_kind")?, r.opt_packet("packet")?)),
+        KEY_LAG_FRAME_CHUNK => Box::new(LagFrameChunk::new(r.int("frame")?, r.int("count")?)),
+        KEY_MOVIE_TRANSITION => Box::new(MovieTransition::new(r.int("movie_frame")?, r.int("transition_kind")?, r.opt_packet("packet")?)),
+        KEY_COMMENT => Box::new(Comment::new(r.string("comment")?)),
+        KEY_EXPERIMENTAL => Box::new(Experimental::new(r.bool("experimental")?)),
+        KEY_UNSPECIFIED => Box::new(Unspecified::new(r.bytes("payload")?)),
+        _ => return Err(Error::Parse(format!("Unknown packet type: {}", fields.name))),
+    };
+    
+    Ok(packet)
+}
+
+pub fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Decodes a string of hexadecimal digits. Whitespace is ignored.
+pub fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) { return Err(Error::Parse("has an odd number of hex digits".to_owned())) }
+    
+    digits.chunks(2).map(|pair| {
+        let pair: String = pair.iter().collect();
+        u8::from_str_radix(&pair, 16).map_err(|_| Error::Parse(format!("contains invalid hex digits: {}", pair)))
+    }).collect()
+}
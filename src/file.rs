@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, Utc};
+use tasd::spec::*;
+use crate::config::Settings;
+use crate::error::Result;
+use crate::provenance;
+use crate::raw::{self, parse_header, parse_raw, split_packets};
+use crate::unknown::UnknownPacket;
+
+/// Reads an existing TASD file.
+///
+/// Unlike `TasdMovie::new`, malformed files are reported as errors instead of panicking, and packets which can't be
+/// parsed are kept byte-for-byte as `UnknownPacket`s. Packets which wouldn't be written back the same way keep their
+/// bytes in a `StoredPacket`. Files with a key width other than 2 are converted to 2-byte keys in memory, and
+/// back to their own key width when saved. Packets whose key doesn't fit in 2 bytes are kept as `UnknownPacket`s with
+/// their original key.
+pub fn load(path: &Path) -> Result<TasdMovie> {
+    let data = std::fs::read(path)?;
+    let header = parse_header(&data)?;
+    let mut packets = Vec::new();
+    for raw in split_packets(&data)? {
+        if header.keylen == 2 { packets.push(parse_raw(&raw)); continue }
+        
+        match raw::rekey(raw.bytes, header.keylen, 2) {
+            Ok(bytes) => packets.push(parse_raw(&raw::split_packet(&bytes, 2)?)),
+            Err(_) => packets.push(Box::new(UnknownPacket::from_raw(&raw, header.keylen))),
+        }
+    }
+    
+    Ok(TasdMovie {
+        version: header.version,
+        keylen: header.keylen,
+        packets,
+        source_path: path.to_owned(),
+    })
+}
+
+/// Loads the file at `path`, or creates a new empty one if nothing exists there yet. Also returns whether the file was
+/// created.
+pub fn load_or_create(path: &Path, settings: &Settings) -> Result<(TasdMovie, bool)> {
+    if path.exists() { return Ok((load(path)?, false)) }
+    
+    let mut tasd = provenance::new_movie(path.to_owned(), settings.new_file_header);
+    save(&mut tasd, settings)?;
+    
+    Ok((tasd, true))
+}
+
+/// Warns about files written for a newer version of the spec than this tool knows, which may not be read correctly.
+pub fn version_warning(tasd: &TasdMovie) -> Option<String> {
+    let latest = u16::from_be_bytes(LATEST_VERSION);
+    (tasd.version > latest).then(|| format!("Warning: This file uses TASD version {:#06X}, but only versions up to {:#06X} are supported. Some packets may not be read correctly.", tasd.version, latest))
+}
+
+/// Number of previous versions kept next to a file, as `<name>.1.tasd.bak` (newest) through `<name>.5.tasd.bak`.
+pub const BACKUP_COUNT: usize = 5;
+
+/// A previous version of a file, created automatically when saving over it.
+pub struct Backup {
+    pub path: PathBuf,
+    pub modified: Option<DateTime<Local>>,
+    pub size: u64,
+}
+
+/// Changes the key width the file is saved with. Fails, leaving the file unchanged, if any key doesn't fit.
+pub fn set_keylen(tasd: &mut TasdMovie, keylen: u8) -> Result<()> {
+    if keylen == 0 || keylen > 8 { return Err(format!("Unsupported key width: {}", keylen).into()) }
+    for packet in &tasd.packets {
+        raw::packet_bytes(packet.as_ref(), keylen)?;
+    }
+    tasd.keylen = keylen;
+    
+    Ok(())
+}
+
+/// Updates the DUMP_LAST_MODIFIED packet (unless disabled) and safely writes the file to its `source_path`.
+pub fn save(tasd: &mut TasdMovie, settings: &Settings) -> Result<()> {
+    if settings.update_last_modified {
+        update_last_modified(tasd);
+    }
+    write(&tasd.source_path, &raw::dump(tasd)?, settings)
+}
+
+/// Replaces the file at `path` without ever leaving it partially written.
+///
+/// The data is written to a temporary file next to the original, which is then renamed over it. If a file already
+/// exists at `path` and it hasn't been backed up with these `settings` yet, a copy of it is kept as the newest backup
+/// first.
+pub fn write(path: &Path, data: &[u8], settings: &Settings) -> Result<()> {
+    if path.is_file() && !settings.is_backed_up(path) {
+        rotate_backups(path, settings)?;
+    }
+    
+    replace(path, data)
+}
+
+fn replace(path: &Path, data: &[u8]) -> Result<()> {
+    let temp = path.with_extension("tasd.tmp");
+    let result = File::create(&temp).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err.into());
+    }
+    std::fs::rename(&temp, path)?;
+    
+    Ok(())
+}
+
+pub fn backup_path(path: &Path, number: usize) -> PathBuf {
+    path.with_extension(format!("{}.tasd.bak", number))
+}
+
+/// Lists the existing backups of the file at `path`, newest first.
+pub fn backups(path: &Path) -> Vec<Backup> {
+    (1..=BACKUP_COUNT).map(|i| backup_path(path, i)).filter_map(|path| {
+        let metadata = std::fs::metadata(&path).ok()?;
+        Some(Backup {
+            modified: metadata.modified().ok().map(DateTime::from),
+            size: metadata.len(),
+            path,
+        })
+    }).collect()
+}
+
+/// Replaces the file with the contents of one of its backups, and reloads it.
+///
+/// The current version of the file is itself backed up first, so a restore can be undone.
+pub fn restore(tasd: &mut TasdMovie, backup: &Path, settings: &Settings) -> Result<()> {
+    let data = std::fs::read(backup)?;
+    load(backup)?; // make sure the backup is readable before replacing anything
+    rotate_backups(&tasd.source_path, settings)?;
+    replace(&tasd.source_path, &data)?;
+    *tasd = load(&tasd.source_path)?;
+    
+    Ok(())
+}
+
+/// Shifts every backup back by one (dropping the oldest), then copies the current file into the newest slot.
+fn rotate_backups(path: &Path, settings: &Settings) -> Result<()> {
+    let oldest = backup_path(path, BACKUP_COUNT);
+    if oldest.exists() {
+        std::fs::remove_file(oldest)?;
+    }
+    for i in (1..BACKUP_COUNT).rev() {
+        let from = backup_path(path, i);
+        if from.exists() {
+            std::fs::rename(&from, backup_path(path, i + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    settings.set_backed_up(path);
+    
+    Ok(())
+}
+
+/// Keeps exactly one DUMP_LAST_MODIFIED packet, set to the current time.
+fn update_last_modified(tasd: &mut TasdMovie) {
+    let mut found = false;
+    tasd.packets.retain(|packet| packet.key() != KEY_DUMP_LAST_MODIFIED || !std::mem::replace(&mut found, true));
+    
+    let packet = Box::new(DumpLastModified::new(Utc::now().timestamp()));
+    match tasd.packets.iter().position(|packet| packet.key() == KEY_DUMP_LAST_MODIFIED) {
+        Some(i) => tasd.packets[i] = packet,
+        None => tasd.packets.push(packet),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::raw::tests::fixture;
+    use crate::raw::Header;
+    
+    /// An empty directory for a single test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tasd-edit-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+    
+    /// The fixture with a different comment, so that each version of a file can be told apart.
+    fn version(n: u8) -> Vec<u8> {
+        let mut data = fixture(2);
+        *data.last_mut().unwrap() = b'0' + n;
+        data
+    }
+    
+    #[test]
+    fn saving_keeps_the_bytes_that_were_loaded() {
+        let dir = test_dir("load-save");
+        let path = dir.join("movie.tasd");
+        for keylen in [2, 4] {
+            std::fs::write(&path, fixture(keylen)).unwrap();
+            let tasd = load(&path).unwrap();
+            let unknown: Vec<bool> = tasd.packets.iter().map(|packet| packet.as_any().is::<UnknownPacket>()).collect();
+            assert_eq!(unknown, [false, false, false, false, true, false, false]);
+            let titles: Vec<&str> = tasd.packets.iter().filter_map(|packet| packet.as_any().downcast_ref::<GameTitle>()).map(|packet| packet.title.as_str()).collect();
+            assert_eq!(titles, ["", "Mario"]);
+            assert_eq!(raw::dump(&tasd).unwrap(), fixture(keylen));
+        }
+        
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn non_minimal_input_chunks_are_still_inputs() {
+        let dir = test_dir("non-minimal");
+        let path = dir.join("movie.tasd");
+        let data = raw::write_file(Header { version: 1, keylen: 2 }, &[
+            vec![0xFE, 0x01, 0x04, 0x00, 0x00, 0x00, 0x03, 0x01, 0xFF, 0xFE],
+        ]);
+        std::fs::write(&path, &data).unwrap();
+        
+        let tasd = load(&path).unwrap();
+        let chunk = tasd.packets[0].as_any().downcast_ref::<InputChunk>().unwrap();
+        assert_eq!((chunk.port, chunk.inputs.as_slice()), (1, [0xFF, 0xFE].as_slice()));
+        assert_eq!(raw::dump(&tasd).unwrap(), data);
+        
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn keeps_keys_that_dont_fit_in_2_bytes() {
+        let dir = test_dir("wide-key");
+        let path = dir.join("movie.tasd");
+        let data = raw::write_file(Header { version: 1, keylen: 4 }, &[
+            vec![0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01],
+            vec![0x00, 0x01, 0x00, 0x05, 0x01, 0x01, 0x42],
+        ]);
+        std::fs::write(&path, &data).unwrap();
+        
+        let mut tasd = load(&path).unwrap();
+        let unknown = tasd.packets.last().unwrap().as_any().downcast_ref::<UnknownPacket>().unwrap();
+        assert_eq!(unknown.key_bytes(), [0x00, 0x01, 0x00, 0x05]);
+        assert_eq!(unknown.payload(), [0x42]);
+        assert_eq!(raw::dump(&tasd).unwrap(), data);
+        assert!(set_keylen(&mut tasd, 2).is_err());
+        
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn backs_up_once_per_session() {
+        let dir = test_dir("backups");
+        let path = dir.join("movie.tasd");
+        let settings = Settings::new(Config::DEFAULT);
+        write(&path, &version(1), &settings).unwrap();
+        assert!(backups(&path).is_empty());
+        
+        for n in 2..=(BACKUP_COUNT as u8 + 2) {
+            write(&path, &version(n), &settings).unwrap();
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), version(BACKUP_COUNT as u8 + 2));
+        assert_eq!(backups(&path).len(), 1);
+        assert_eq!(std::fs::read(backup_path(&path, 1)).unwrap(), version(1));
+        
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn restore_backs_up_the_current_version() {
+        let dir = test_dir("restore");
+        let path = dir.join("movie.tasd");
+        let settings = Settings::new(Config::DEFAULT);
+        write(&path, &version(1), &settings).unwrap();
+        write(&path, &version(2), &settings).unwrap();
+        
+        let mut tasd = load(&path).unwrap();
+        restore(&mut tasd, &backup_path(&path, 1), &settings).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), version(1));
+        assert_eq!(std::fs::read(backup_path(&path, 1)).unwrap(), version(2));
+        assert_eq!(std::fs::read(backup_path(&path, 2)).unwrap(), version(1));
+        assert_eq!(tasd.packets.len(), 7);
+        
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use regex::Regex;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::fields::{key_from_name, to_fields, PacketFields, Value};
+use crate::input::{frame_size, port_controllers};
+
+/// Criteria for narrowing down the packet list. Every criteria which is set must match; an empty filter matches all.
+#[derive(Default)]
+pub struct PacketFilter {
+    /// Only packets with one of these keys (empty for any key).
+    pub keys: Vec<Key>,
+    /// Never packets with one of these keys.
+    pub exclude_keys: Vec<Key>,
+    /// Only packets for this controller port.
+    pub port: Option<u8>,
+    /// Only frame-indexed packets which cover at least one frame in this inclusive range.
+    pub frames: Option<(u64, u64)>,
+    /// Only packets with a text field matching this pattern.
+    pub pattern: Option<Regex>,
+}
+impl PacketFilter {
+    /// Indexes of every packet which matches the filter.
+    pub fn apply(&self, tasd: &TasdMovie) -> Vec<usize> {
+        let controllers = port_controllers(&tasd.packets);
+        let mut next_frame: HashMap<u8, u64> = HashMap::new();
+        
+        let mut out = Vec::new();
+        for (i, packet) in tasd.packets.iter().enumerate() {
+            // INPUT_CHUNK frames are numbered by counting every earlier chunk for the same port
+            let frames = match packet.as_any().downcast_ref::<InputChunk>() {
+                Some(chunk) => {
+                    let count = chunk.inputs.chunks(controllers.get(&chunk.port).copied().map(frame_size).unwrap_or(1)).count() as u64;
+                    let first = next_frame.entry(chunk.port).or_insert(0);
+                    let frames = if count == 0 { None } else { Some((*first, *first + count - 1)) };
+                    *first += count;
+                    frames
+                },
+                None => frame_span(packet.as_ref()),
+            };
+            
+            if self.matches(packet.as_ref(), frames) { out.push(i) }
+        }
+        
+        out
+    }
+    
+    fn matches(&self, packet: &dyn Packet, frames: Option<(u64, u64)>) -> bool {
+        if !self.keys.is_empty() && !self.keys.contains(&packet.key()) { return false }
+        if self.exclude_keys.contains(&packet.key()) { return false }
+        if let Some(port) = self.port {
+            if packet_port(packet) != Some(port) { return false }
+        }
+        if let Some((start, end)) = self.frames {
+            match frames {
+                Some((first, last)) if first <= end && last >= start => (),
+                _ => return false,
+            }
+        }
+        if let Some(pattern) = &self.pattern {
+            if !text_fields(&to_fields(packet)).iter().any(|text| pattern.is_match(text)) { return false }
+        }
+        
+        true
+    }
+}
+
+/// Parses a comma or space separated list of packet names (e.g. `GAME_TITLE, COMMENT`) or hexadecimal keys (`0x0101`).
+pub fn parse_keys(text: &str) -> Result<Vec<Key>> {
+    text.split(|c: char| c == ',' || c.is_whitespace()).filter(|name| !name.is_empty()).map(|name| {
+        match name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16).map(|key| key.to_be_bytes()).map_err(|_| Error::Parse(format!("Invalid key: {}", name))),
+            None => key_from_name(name).ok_or_else(|| Error::Parse(format!("Unknown packet type: {}", name))),
+        }
+    }).collect()
+}
+
+/// Parses an inclusive frame range such as `100-200`, `100-` (100 onwards), or `100` (a single frame).
+pub fn parse_range(text: &str) -> Result<(u64, u64)> {
+    let err = |_| Error::Parse(format!("Invalid frame range: {}", text));
+    match text.split_once('-') {
+        Some((start, end)) => {
+            let start = start.trim().parse::<u64>().map_err(err)?;
+            let end = if end.trim().is_empty() { u64::MAX } else { end.trim().parse::<u64>().map_err(err)? };
+            if start > end { return Err(Error::Parse(format!("Invalid frame range: {}", text))) }
+            Ok((start, end))
+        },
+        None => text.trim().parse::<u64>().map(|frame| (frame, frame)).map_err(err),
+    }
+}
+
+/// The controller port a packet applies to, if any.
+fn packet_port(packet: &dyn Packet) -> Option<u8> {
+    let any = packet.as_any();
+    any.downcast_ref::<PortController>().map(|packet| packet.port)
+        .or_else(|| any.downcast_ref::<InputChunk>().map(|packet| packet.port))
+        .or_else(|| any.downcast_ref::<InputMoment>().map(|packet| packet.port))
+}
+
+/// Inclusive range of frames covered by packets which are indexed by frame, other than INPUT_CHUNK.
+fn frame_span(packet: &dyn Packet) -> Option<(u64, u64)> {
+    let any = packet.as_any();
+    if let Some(moment) = any.downcast_ref::<InputMoment>() {
+        return (moment.kind == 0x01).then_some((moment.index, moment.index));
+    }
+    if let Some(transition) = any.downcast_ref::<Transition>() {
+        return (transition.index_kind == 0x01).then_some((transition.index, transition.index));
+    }
+    if let Some(transition) = any.downcast_ref::<MovieTransition>() {
+        return Some((transition.movie_frame as u64, transition.movie_frame as u64));
+    }
+    if let Some(lag) = any.downcast_ref::<LagFrameChunk>() {
+        return (lag.count > 0).then_some((lag.frame as u64, lag.frame as u64 + lag.count as u64 - 1));
+    }
+    
+    None
+}
+
+/// Every string field of the packet, including those of an embedded packet.
+fn text_fields(fields: &PacketFields) -> Vec<&str> {
+    let mut out = Vec::new();
+    for (_, value) in &fields.fields {
+        match value {
+            Value::Str(text) => out.push(text.as_str()),
+            Value::Packet(fields) => out.extend(text_fields(fields)),
+            _ => (),
+        }
+    }
+    
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    
+    fn movie() -> TasdMovie {
+        TasdMovie {
+            version: 1,
+            keylen: 2,
+            packets: vec![
+                Box::new(GameTitle::new("Super Mario Bros.".to_owned())),
+                Box::new(PortController::new(1, 0x0101)),
+                Box::new(PortController::new(2, 0x0101)),
+                Box::new(InputChunk::new(1, vec![0xFF; 10])),
+                Box::new(InputChunk::new(2, vec![0xFF; 5])),
+                Box::new(InputChunk::new(1, vec![0xFF; 10])),
+                Box::new(InputMoment::new(2, 0x01, 30, vec![0x7F])),
+                Box::new(LagFrameChunk::new(40, 0)),
+                Box::new(Comment::new("Route by Mario".to_owned())),
+            ],
+            source_path: PathBuf::from("movie.tasd"),
+        }
+    }
+    
+    #[test]
+    fn parses_names_and_hex_keys() {
+        assert_eq!(parse_keys("GAME_TITLE, COMMENT").unwrap(), [KEY_GAME_TITLE, KEY_COMMENT]);
+        assert_eq!(parse_keys(" 0x0001  0XFE01 ").unwrap(), [KEY_CONSOLE_TYPE, KEY_INPUT_CHUNK]);
+        assert!(parse_keys("").unwrap().is_empty());
+        assert_eq!(parse_keys("GAME_TITLE,NOPE").unwrap_err().to_string(), "Invalid input: Unknown packet type: NOPE");
+        assert!(parse_keys("0xZZ").is_err());
+    }
+    
+    #[test]
+    fn parses_frame_ranges() {
+        assert_eq!(parse_range("100-200").unwrap(), (100, 200));
+        assert_eq!(parse_range(" 100 - ").unwrap(), (100, u64::MAX));
+        assert_eq!(parse_range("42").unwrap(), (42, 42));
+        assert!(parse_range("200-100").is_err());
+        assert!(parse_range("-100").is_err());
+        assert!(parse_range("a-b").is_err());
+    }
+    
+    #[test]
+    fn empty_filter_matches_everything() {
+        let tasd = movie();
+        assert_eq!(PacketFilter::default().apply(&tasd), (0..tasd.packets.len()).collect::<Vec<usize>>());
+    }
+    
+    #[test]
+    fn filters_by_key_and_port() {
+        let tasd = movie();
+        let filter = PacketFilter { keys: vec![KEY_INPUT_CHUNK, KEY_INPUT_MOMENT], port: Some(2), ..Default::default() };
+        assert_eq!(filter.apply(&tasd), [4, 6]);
+        let filter = PacketFilter { exclude_keys: vec![KEY_INPUT_CHUNK, KEY_PORT_CONTROLLER], ..Default::default() };
+        assert_eq!(filter.apply(&tasd), [0, 6, 7, 8]);
+    }
+    
+    #[test]
+    fn numbers_chunk_frames_per_port() {
+        let tasd = movie();
+        // port 1's second chunk covers frames 10-19, even though port 2's chunk comes between them
+        let filter = PacketFilter { frames: Some((12, 12)), ..Default::default() };
+        assert_eq!(filter.apply(&tasd), [5]);
+        let filter = PacketFilter { frames: Some((4, 30)), ..Default::default() };
+        assert_eq!(filter.apply(&tasd), [3, 4, 5, 6]);
+        // an empty lag chunk covers no frames
+        let filter = PacketFilter { frames: Some((40, 40)), ..Default::default() };
+        assert!(filter.apply(&tasd).is_empty());
+    }
+    
+    #[test]
+    fn matches_patterns_against_text_fields() {
+        let tasd = movie();
+        let filter = PacketFilter { pattern: Some(Regex::new("(?i)mario").unwrap()), ..Default::default() };
+        assert_eq!(filter.apply(&tasd), [0, 8]);
+        let filter = PacketFilter { pattern: Some(Regex::new("^Route").unwrap()), keys: vec![KEY_GAME_TITLE], ..Default::default() };
+        assert!(filter.apply(&tasd).is_empty());
+    }
+}
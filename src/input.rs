@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+
+/// Number of bytes a single frame of input occupies for the given controller type (see `controller_type_lut`).
+///
+/// Unknown or reserved controller types are treated as a single byte per frame.
+pub fn frame_size(controller_kind: u16) -> usize {
+    match controller_kind {
+        0x0101 => 1, // NES Standard Controller
+        0x0102 => 2, // NES Four Score (two controllers per port)
+        0x0103 => 1, // NES Zapper
+        0x0201 => 2, // SNES Standard Controller
+        0x0202 => 8, // SNES Super Multitap (four controllers)
+        0x0203 => 4, // SNES Mouse
+        0x0301..=0x0305 | 0x0308 => 4, // N64 controllers, mouse, and Densha de Go
+        0x0401 => 8, // GC Standard Controller
+        0x0501 | 0x0601 => 1, // GB/GBC Gamepad
+        0x0701 => 2, // GBA Gamepad
+        0x0801 => 1, // Genesis 3-Button
+        0x0802 => 2, // Genesis 6-Button
+        0x0901 => 1, // A2600 Joystick
+        0x0903 => 2, // A2600 Keyboard Controller
+        _ => 1,
+    }
+}
+
+const NES: &str = "ABsSUDLR";
+const SNES_HIGH: &str = "BYsSUDLR";
+const SNES_LOW: &str = "AXlr****";
+
+/// Button mnemonics for each byte of a frame, most significant bit first.
+///
+/// Only controllers made up entirely of digital buttons have a layout, and bits which aren't mapped to a button are
+/// shown as `*`. Input data is active-low, so a cleared bit means the button is pressed.
+pub fn button_layout(controller_kind: u16) -> Option<Vec<&'static str>> {
+    Some(match controller_kind {
+        0x0101 => vec![NES],
+        0x0102 => vec![NES, NES],
+        0x0201 => vec![SNES_HIGH, SNES_LOW],
+        0x0202 => vec![SNES_HIGH, SNES_LOW, SNES_HIGH, SNES_LOW, SNES_HIGH, SNES_LOW, SNES_HIGH, SNES_LOW],
+        0x0501 | 0x0601 => vec!["DULRSsBA"],
+        0x0701 => vec!["******LR", "DULRSsBA"],
+        0x0801 => vec!["UDLRABCS"],
+        0x0802 => vec!["UDLRABCS", "XYZM****"],
+        0x0901 => vec!["UDLRF***"],
+        _ => return None,
+    })
+}
+
+/// Formats a single frame as button mnemonics, with `.` for each released button (e.g. `A..S.D..`).
+///
+/// Frames without a known button layout are formatted as hexadecimal, prefixed with `$`.
+pub fn format_frame(controller_kind: Option<u16>, frame: &[u8]) -> String {
+    match controller_kind.and_then(button_layout) {
+        Some(layout) if layout.len() == frame.len() => {
+            let mut out = String::new();
+            for (byte, buttons) in frame.iter().zip(layout) {
+                for (bit, button) in buttons.chars().enumerate() {
+                    out.push(if byte & (0x80 >> bit) == 0 { button } else { '.' });
+                }
+            }
+            out
+        },
+        _ => format!("${}", frame.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()),
+    }
+}
+
+/// Parses a frame created by `format_frame`. Whitespace is ignored.
+///
+/// Mnemonics are read by position only; `.` is a released button and any other character is a pressed one.
+pub fn parse_frame(text: &str) -> Result<Vec<u8>> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if let Some(hex) = text.strip_prefix('$') {
+        return crate::fields::decode_hex(hex).map_err(|err| err.context("Frame"));
+    }
+    
+    let bits: Vec<char> = text.chars().collect();
+    if bits.is_empty() || !bits.len().is_multiple_of(8) {
+        return Err(Error::Parse(format!("Frame '{}' must have 8 buttons per byte", text)));
+    }
+    
+    Ok(bits.chunks(8).map(|byte| byte.iter().fold(0u8, |acc, c| (acc << 1) | (*c == '.') as u8)).collect())
+}
+
+/// Buttons held in the frame, as `(controller, mnemonic)` pairs where `controller` is 0 unless the controller type
+/// combines several controllers (e.g. the Four Score). Analog positions and movement aren't included.
+pub fn pressed_buttons(controller_kind: Option<u16>, frame: &[u8]) -> Vec<(usize, char)> {
+    let mut out = Vec::new();
+    match (controller_kind, frame) {
+        (Some(0x0103), [byte]) => if byte & 0x10 != 0 { out.push((0, 'T')) },
+        (Some(0x0203), [_, buttons, _, _]) => {
+            if buttons & 0x40 == 0 { out.push((0, 'L')) }
+            if buttons & 0x80 == 0 { out.push((0, 'R')) }
+        },
+        (Some(0x0301..=0x0305), [high, low, _, _]) => {
+            for (byte, buttons) in [(high, N64_HIGH), (low, N64_LOW)] {
+                for (bit, button) in buttons.chars().enumerate() {
+                    if byte & (0x80 >> bit) != 0 && button != '*' { out.push((0, button)) }
+                }
+            }
+        },
+        (kind, frame) => if let Some(layout) = kind.and_then(button_layout).filter(|layout| layout.len() == frame.len()) {
+            let bytes_per_controller = layout.len() / controller_count(kind.unwrap());
+            for (i, (byte, buttons)) in frame.iter().zip(layout).enumerate() {
+                for (bit, button) in buttons.chars().enumerate() {
+                    if byte & (0x80 >> bit) == 0 && button != '*' { out.push((i / bytes_per_controller, button)) }
+                }
+            }
+        },
+    }
+    
+    out
+}
+
+/// Whether nothing is being pressed or moved. Frames of unknown controller types are idle if every bit is released.
+pub fn is_idle(controller_kind: Option<u16>, frame: &[u8]) -> bool {
+    if !pressed_buttons(controller_kind, frame).is_empty() { return false }
+    
+    match (controller_kind, frame) {
+        (Some(0x0103), [_]) => true,
+        (Some(0x0203), [_, _, y, x]) => (y ^ 0xFF) & 0x7F == 0 && (x ^ 0xFF) & 0x7F == 0,
+        (Some(0x0301..=0x0305), [_, _, x, y]) => *x == 0 && *y == 0,
+        (kind, frame) if kind.and_then(button_layout).is_some_and(|layout| layout.len() == frame.len()) => true,
+        _ => frame.iter().all(|byte| *byte == 0xFF),
+    }
+}
+
+/// Number of separate controllers making up a single controller type (e.g. 4 for the SNES Super Multitap).
+pub fn controller_count(controller_kind: u16) -> usize {
+    match controller_kind {
+        0x0102 => 2,
+        0x0202 => 4,
+        _ => 1,
+    }
+}
+
+/// Human-readable form of a single frame, for display only (see `format_frame` for a form that can be parsed back).
+///
+/// Digital controllers are shown as button strings. The Zapper, mice, and N64 controllers also show their light sensor,
+/// movement, or analog stick, and anything else falls back to hexadecimal.
+pub fn decode_frame(controller_kind: Option<u16>, frame: &[u8]) -> String {
+    match (controller_kind, frame) {
+        // $4017 as read by the console: D4 is set while the trigger is pulled, D3 is cleared while light is detected
+        (Some(0x0103), [byte]) => format!("{}{}", if byte & 0x10 != 0 { 'T' } else { '.' }, if byte & 0x08 == 0 { 'L' } else { '.' }),
+        (Some(0x0203), [_, buttons, y, x]) => {
+            // the mouse's report is active-low like the rest of the SNES's serial data
+            let (buttons, y, x) = (buttons ^ 0xFF, y ^ 0xFF, x ^ 0xFF);
+            format!("{}{} x={:+} y={:+}", if buttons & 0x40 != 0 { 'L' } else { '.' }, if buttons & 0x80 != 0 { 'R' } else { '.' }, mouse_axis(x), mouse_axis(y))
+        },
+        // the controller's status response, in which a set bit means pressed, followed by the signed stick position
+        (Some(0x0301..=0x0305), [high, low, x, y]) => {
+            let mut out = String::new();
+            for (byte, buttons) in [(high, N64_HIGH), (low, N64_LOW)] {
+                for (bit, button) in buttons.chars().enumerate() {
+                    out.push(if byte & (0x80 >> bit) != 0 { button } else { '.' });
+                }
+            }
+            format!("{} x={:+} y={:+}", out, *x as i8, *y as i8)
+        },
+        _ => format_frame(controller_kind, frame),
+    }
+}
+
+const N64_HIGH: &str = "ABZSUDLR";
+const N64_LOW: &str = "**LRudlr";
+
+/// SNES mouse movement is a direction bit (set for up/left) followed by a 7-bit magnitude.
+fn mouse_axis(value: u8) -> i16 {
+    let magnitude = (value & 0x7F) as i16;
+    if value & 0x80 != 0 { -magnitude } else { magnitude }
+}
+
+/// Collapses consecutive identical frames into `(first frame, last frame, decoded frame)` runs, numbering frames from
+/// `first_frame`.
+pub fn decode_runs(controller_kind: Option<u16>, inputs: &[u8], first_frame: usize) -> Vec<(usize, usize, String)> {
+    let size = controller_kind.map(frame_size).unwrap_or(1);
+    let mut runs: Vec<(usize, usize, &[u8])> = Vec::new();
+    for (i, frame) in inputs.chunks(size).enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.2 == frame => run.1 = first_frame + i,
+            _ => runs.push((first_frame + i, first_frame + i, frame)),
+        }
+    }
+    
+    runs.into_iter().map(|(first, last, frame)| (first, last, decode_frame(controller_kind, frame))).collect()
+}
+
+/// Concatenates all INPUT_CHUNK data for a single port.
+pub fn port_inputs(tasd: &TasdMovie, port: u8) -> Vec<u8> {
+    let mut out = Vec::new();
+    for packet in tasd.search_by_key(vec![KEY_INPUT_CHUNK]) {
+        if let Some(chunk) = packet.as_any().downcast_ref::<InputChunk>() {
+            if chunk.port == port { out.extend_from_slice(&chunk.inputs); }
+        }
+    }
+    
+    out
+}
+
+/// Controller type of each port, taken from the first PORT_CONTROLLER packet for that port.
+pub fn port_controllers(packets: &[Box<dyn Packet>]) -> HashMap<u8, u16> {
+    let mut controllers = HashMap::new();
+    for packet in packets {
+        if let Some(controller) = packet.as_any().downcast_ref::<PortController>() {
+            controllers.entry(controller.port).or_insert(controller.kind);
+        }
+    }
+    
+    controllers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn formats_frames_as_buttons() {
+        // active-low: A, Start, and Right are held
+        assert_eq!(format_frame(Some(0x0101), &[0b0110_1110]), "A..S...R");
+        assert_eq!(format_frame(Some(0x0201), &[0xFF, 0x7F]), "........A.......");
+        assert_eq!(format_frame(None, &[0x12, 0xAB]), "$12AB");
+        // a frame which doesn't fit the layout falls back to hex
+        assert_eq!(format_frame(Some(0x0101), &[0x00, 0x00]), "$0000");
+    }
+    
+    #[test]
+    fn parses_formatted_frames() {
+        for (kind, frame) in [(Some(0x0101), vec![0b0110_1110]), (Some(0x0202), vec![0x00, 0xFF, 0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC]), (None, vec![0x01, 0x02, 0x03])] {
+            assert_eq!(parse_frame(&format_frame(kind, &frame)).unwrap(), frame);
+        }
+        assert_eq!(parse_frame("A... S... ").unwrap(), [0b0111_0111]);
+    }
+    
+    #[test]
+    fn rejects_malformed_frames() {
+        assert_eq!(parse_frame("A..S").unwrap_err().to_string(), "Invalid input: Frame 'A..S' must have 8 buttons per byte");
+        assert!(parse_frame("$ABC").is_err());
+        assert!(parse_frame("").is_err());
+    }
+    
+    #[test]
+    fn decodes_analog_controllers() {
+        assert_eq!(decode_frame(Some(0x0103), &[0x10]), "TL");
+        assert_eq!(decode_frame(Some(0x0301), &[0x80, 0x00, 0x05, 0xFB]), "A............... x=+5 y=-5");
+        assert!(is_idle(Some(0x0301), &[0x00, 0x00, 0x00, 0x00]));
+        assert!(!is_idle(Some(0x0301), &[0x00, 0x00, 0x01, 0x00]));
+    }
+    
+    #[test]
+    fn collapses_identical_frames_into_runs() {
+        let runs = decode_runs(Some(0x0101), &[0xFF, 0xFF, 0x7F, 0xFF], 10);
+        assert_eq!(runs, [(10, 11, "........".to_owned()), (12, 12, "A.......".to_owned()), (13, 13, "........".to_owned())]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::Path;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::fields::{encode_hex, key_name, to_fields, Value, UNKNOWN_NAME};
+use crate::raw::{hex_dump, parse_header, parse_packet, split_packets, OVERSIZED_KEY};
+use crate::text::format_value;
+use crate::unknown::UnknownPacket;
+
+/// Byte arrays longer than this are shortened in the field breakdown. The hex dump always shows every byte.
+const FIELD_BYTES_LIMIT: usize = 32;
+
+/// A single packet exactly as it's stored in a file.
+pub struct Inspection {
+    pub index: usize,
+    /// Position of the packet in the file.
+    pub offset: usize,
+    pub keylen: u8,
+    /// The entire serialized packet.
+    pub bytes: Vec<u8>,
+    /// Index into `bytes` where the payload begins.
+    pub payload_start: usize,
+    pub packet: Box<dyn Packet>,
+}
+impl Inspection {
+    /// Finds packet `index` in the contents of a TASD file.
+    pub fn new(data: &[u8], index: usize) -> Result<Self> {
+        let header = parse_header(data)?;
+        let raws = split_packets(data)?;
+        let raw = raws.get(index).ok_or_else(|| Error::Other(format!("There's no packet #{}, the file has {} packets.", index, raws.len())))?;
+        
+        Ok(Self {
+            index,
+            offset: raw.offset,
+            keylen: header.keylen,
+            bytes: raw.bytes.to_vec(),
+            payload_start: raw.payload_start,
+            packet: match raw.key {
+                OVERSIZED_KEY => Box::new(UnknownPacket::from_raw(raw, header.keylen)),
+                key => parse_packet(key, raw.payload()),
+            },
+        })
+    }
+    
+    pub fn payload(&self) -> &[u8] {
+        &self.bytes[self.payload_start..]
+    }
+    
+    /// Breakdown of the packet's key, length encoding, and fields, followed by a hex dump of the whole packet.
+    pub fn describe(&self) -> Vec<String> {
+        let keylen = self.keylen as usize;
+        let key = self.packet.key();
+        let exp = self.bytes[keylen];
+        let length = &self.bytes[(keylen + 1)..self.payload_start];
+        
+        let mut out = vec![
+            format!("Packet #{} at offset {:#X}, {} bytes", self.index, self.offset, self.bytes.len()),
+            format!("  Key:             {}  ({})", spaced_hex(&self.bytes[..keylen]), key_name(key).unwrap_or(UNKNOWN_NAME)),
+            format!("  Length exponent: {:02X}  (length is {} byte(s))", exp, exp),
+            format!("  Length:          {}  ({} bytes)", if length.is_empty() { "none".to_owned() } else { spaced_hex(length) }, self.payload().len()),
+            format!("  Payload:         starts at offset {:#X}", self.offset + self.payload_start),
+        ];
+        
+        let fields = to_fields(self.packet.as_ref());
+        if !fields.fields.is_empty() {
+            out.push("Fields:".to_owned());
+            for (name, value) in &fields.fields {
+                let text = match value {
+                    Value::Bytes(bytes) if bytes.len() > FIELD_BYTES_LIMIT => format!("hex:{}... ({} bytes)", encode_hex(&bytes[..FIELD_BYTES_LIMIT]), bytes.len()),
+                    value => format_value(name, value),
+                };
+                out.push(format!("  {}: {}", name, text));
+            }
+        }
+        
+        out.push(String::new());
+        out.extend(hex_dump(&self.bytes, self.offset));
+        out
+    }
+    
+    /// The data an UNSPECIFIED, MOVIE_FILE, or MEMORY_INIT packet carries, along with a file name to save it as.
+    pub fn extractable(&self) -> Option<(String, &[u8])> {
+        let any = self.packet.as_any();
+        if let Some(packet) = any.downcast_ref::<Unspecified>() {
+            return Some((format!("packet{}.bin", self.index), &packet.payload));
+        }
+        if let Some(packet) = any.downcast_ref::<MovieFile>() {
+            return Some((file_name(&packet.name).unwrap_or_else(|| format!("packet{}.movie", self.index)), &packet.data));
+        }
+        if let Some(packet) = any.downcast_ref::<MemoryInit>() {
+            let data = packet.data.as_deref()?;
+            return Some((file_name(&packet.name).map(|name| format!("{}.bin", name)).unwrap_or_else(|| format!("packet{}.bin", self.index)), data));
+        }
+        
+        None
+    }
+}
+
+fn spaced_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+/// Only the final component of a name stored in the file, so that extracting never writes outside the chosen directory.
+fn file_name(name: &str) -> Option<String> {
+    Path::new(name).file_name().map(|name| name.to_string_lossy().to_string()).filter(|name| !name.trim().is_empty())
+}
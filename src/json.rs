@@ -0,0 +1,171 @@
+use serde_json::{json, Map, Number};
+use crate::error::{Error, Result};
+use crate::fields::{decode_hex, encode_hex, from_fields, to_fields, PacketFields, Value};
+use crate::raw::{is_canonical, parse_header, parse_packet, rekey, split_packet, split_packets, write_file, Header, RawPacket, OVERSIZED_KEY};
+use crate::unknown::UnknownPacket;
+
+/// Converts the contents of a TASD file into JSON.
+///
+/// Every packet is written as an object containing its `type` and each of its fields, with binary data written as
+/// hexadecimal strings. Packets which wouldn't re-serialize to the exact same bytes (e.g. non-minimal length encoding)
+/// also carry a `raw` copy of the original bytes, so that converting back always produces an identical file.
+pub fn to_json(data: &[u8]) -> Result<serde_json::Value> {
+    let header = parse_header(data)?;
+    let mut packets = Vec::new();
+    for raw in split_packets(data)? {
+        let packet = parse_packet(raw.key, raw.payload());
+        let mut object = packet_json(&raw, header.keylen);
+        if !is_canonical(packet.as_ref(), &raw, header.keylen) {
+            object.insert("raw".to_owned(), json!(encode_hex(raw.bytes)));
+        }
+        packets.push(serde_json::Value::Object(object));
+    }
+    
+    Ok(json!({
+        "version": header.version,
+        "keylen": header.keylen,
+        "packets": packets,
+    }))
+}
+
+/// Converts JSON produced by `to_json` back into the contents of a TASD file.
+///
+/// A packet's `raw` bytes are only used while its other fields still match them. Once the fields are edited, the packet
+/// is built from its fields instead.
+pub fn from_json(json: &serde_json::Value) -> Result<Vec<u8>> {
+    let missing = |name: &str| Error::Parse(format!("Missing or invalid '{}'", name));
+    let version = json.get("version").and_then(|v| v.as_u64()).and_then(|v| u16::try_from(v).ok()).ok_or_else(|| missing("version"))?;
+    let keylen = json.get("keylen").and_then(|v| v.as_u64()).and_then(|v| u8::try_from(v).ok()).ok_or_else(|| missing("keylen"))?;
+    let list = json.get("packets").and_then(|v| v.as_array()).ok_or_else(|| missing("packets"))?;
+    
+    let mut packets = Vec::new();
+    for (i, packet) in list.iter().enumerate() {
+        let err = |err: Error| err.context(format!("Packet #{}", i));
+        let object = packet.as_object().ok_or_else(|| Error::Parse(format!("Packet #{} is not an object", i)))?;
+        let raw = match object.get("raw") {
+            Some(raw) => Some(decode_hex(raw.as_str().ok_or_else(|| err(Error::Parse("'raw' must be a string".to_owned())))?).map_err(|e| err(e.context("'raw'")))?),
+            None => None,
+        };
+        if let Some(raw) = raw.filter(|raw| matches_fields(raw, keylen, object)) {
+            packets.push(raw);
+        } else {
+            let fields = json_to_fields(object).map_err(|e| err(Error::Parse(e)))?;
+            let packet = from_fields(&fields).map_err(err)?.raw();
+            packets.push(rekey(&packet, 2, keylen).map_err(err)?);
+        }
+    }
+    
+    Ok(write_file(Header { version, keylen }, &packets))
+}
+
+/// The fields of a packet read from a file, as written by `to_json`.
+fn packet_json(raw: &RawPacket, keylen: u8) -> Map<String, serde_json::Value> {
+    match raw.key {
+        OVERSIZED_KEY => fields_to_json(&to_fields(&UnknownPacket::from_raw(raw, keylen))),
+        key => fields_to_json(&to_fields(parse_packet(key, raw.payload()).as_ref())),
+    }
+}
+
+/// Whether a packet's fields (apart from `raw`) are still the ones `to_json` wrote for its raw bytes.
+fn matches_fields(raw: &[u8], keylen: u8, object: &Map<String, serde_json::Value>) -> bool {
+    let Ok(raw) = split_packet(raw, keylen) else { return false };
+    let mut fields = object.clone();
+    fields.remove("raw");
+    packet_json(&raw, keylen) == fields
+}
+
+fn fields_to_json(fields: &PacketFields) -> Map<String, serde_json::Value> {
+    let mut object = Map::new();
+    object.insert("type".to_owned(), json!(fields.name));
+    for (name, value) in &fields.fields {
+        object.insert(name.clone(), value_to_json(value));
+    }
+    
+    object
+}
+
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::None => serde_json::Value::Null,
+        Value::Bool(value) => json!(value),
+        Value::Int(value) => match u64::try_from(*value) {
+            Ok(value) => json!(value),
+            Err(_) => json!(*value as i64),
+        },
+        Value::Str(value) => json!(value),
+        Value::Bytes(value) => json!(encode_hex(value)),
+        Value::List(value) => json!(value),
+        Value::Packet(fields) => serde_json::Value::Object(fields_to_json(fields)),
+    }
+}
+
+fn json_to_fields(object: &Map<String, serde_json::Value>) -> std::result::Result<PacketFields, String> {
+    let name = object.get("type").and_then(|v| v.as_str()).ok_or("Missing or invalid 'type'")?;
+    let mut fields = Vec::new();
+    for (field, value) in object.iter().filter(|(field, _)| *field != "type") {
+        fields.push((field.clone(), json_to_value(value).map_err(|err| format!("'{}' {}", field, err))?));
+    }
+    
+    Ok(PacketFields { name: name.to_owned(), fields })
+}
+
+fn json_to_value(value: &serde_json::Value) -> std::result::Result<Value, String> {
+    Ok(match value {
+        serde_json::Value::Null => Value::None,
+        serde_json::Value::Bool(value) => Value::Bool(*value),
+        serde_json::Value::Number(value) => Value::Int(number(value)?),
+        serde_json::Value::String(value) => Value::Str(value.clone()),
+        serde_json::Value::Array(list) => Value::List(list.iter().map(|v| v.as_u64().ok_or("must only contain whole numbers")).collect::<std::result::Result<_, _>>()?),
+        serde_json::Value::Object(object) => Value::Packet(Box::new(json_to_fields(object)?)),
+    })
+}
+
+fn number(value: &Number) -> std::result::Result<i128, String> {
+    if let Some(value) = value.as_u64() { return Ok(value as i128) }
+    if let Some(value) = value.as_i64() { return Ok(value as i128) }
+    
+    Err("must be a whole number".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::fixture;
+    
+    #[test]
+    fn round_trip_is_exact() {
+        for keylen in [2, 4] {
+            let data = fixture(keylen);
+            let json = to_json(&data).unwrap();
+            assert_eq!(from_json(&json).unwrap(), data, "keylen {}", keylen);
+            
+            let text = serde_json::to_string_pretty(&json).unwrap();
+            assert_eq!(from_json(&serde_json::from_str(&text).unwrap()).unwrap(), data, "keylen {}", keylen);
+        }
+    }
+    
+    #[test]
+    fn only_non_canonical_packets_keep_raw_bytes() {
+        let json = to_json(&fixture(2)).unwrap();
+        let raw: Vec<bool> = json["packets"].as_array().unwrap().iter().map(|packet| packet.get("raw").is_some()).collect();
+        assert_eq!(raw, [false, false, true, true, false, false, false]);
+    }
+    
+    #[test]
+    fn edited_fields_are_written() {
+        let mut json = to_json(&fixture(2)).unwrap();
+        json["packets"][6]["comment"] = json!("edited");
+        let data = from_json(&json).unwrap();
+        assert!(data.ends_with(&[0xFF, 0x01, 0x01, 0x06, b'e', b'd', b'i', b't', b'e', b'd']));
+    }
+    
+    #[test]
+    fn edited_fields_replace_raw_bytes() {
+        let mut json = to_json(&fixture(2)).unwrap();
+        json["packets"][3]["title"] = json!("Luigi");
+        let data = from_json(&json).unwrap();
+        let packets = split_packets(&data).unwrap();
+        assert_eq!(packets[3].bytes, [0x00, 0x03, 0x01, 0x05, b'L', b'u', b'i', b'g', b'i']);
+        assert_eq!(packets[2].bytes, [0x00, 0x03, 0x01, 0x00]);
+    }
+}
@@ -0,0 +1,249 @@
+use std::cmp::max;
+use std::path::Path;
+use std::sync::LazyLock;
+use regex::Regex;
+use tasd::spec::*;
+use crate::convert::{Exporter, FormatInfo, Importer};
+use crate::error::{Error, Result};
+
+/// NES input log with two controllers (.r08).
+pub struct R08;
+impl Importer for R08 {
+    fn metadata(&self) -> &'static FormatInfo {
+        &FormatInfo { name: "r08", description: "NES (.r08)", extensions: &["r08"] }
+    }
+    
+    fn console_types(&self) -> &'static [u8] { &[0x01] }
+    
+    fn import(&self, _path: &Path, data: &[u8], _console_type: u8) -> Result<Vec<Box<dyn Packet>>> {
+        let mut packets: Vec<Box<dyn Packet>> = Vec::new();
+        let mut result = data.to_vec();
+        if result.len() % 2 == 1 { result.push(0xFF) } // Shouldn't ever be misaligned, but is safer to double check
+        packets.push(Box::new(ConsoleType::new(0x01, None)));
+        packets.push(Box::new(PortController::new(1, 0x0101)));
+        packets.push(Box::new(PortController::new(2, 0x0101)));
+        
+        let mut port1 = Vec::new();
+        let mut port2 = Vec::new();
+        for i in 0..(result.len() / 2) {
+            port1.push(result[i * 2] ^ 0xFF);
+            port2.push(result[(i * 2) + 1] ^ 0xFF);
+        }
+        packets.push(Box::new(InputChunk::new(1, port1)));
+        packets.push(Box::new(InputChunk::new(2, port2)));
+        
+        Ok(packets)
+    }
+}
+impl Exporter for R08 {
+    fn metadata(&self) -> &'static FormatInfo { Importer::metadata(self) }
+    
+    fn console_types(&self) -> &'static [u8] { &[0x01] }
+    
+    fn export(&self, tasd: &TasdMovie, _console_type: u8) -> Result<Vec<u8>> {
+        let chunks = tasd.search_by_key(vec![KEY_INPUT_CHUNK]);
+        let chunks: Vec<&InputChunk> = chunks.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>()).collect();
+        
+        let mut port1 = Vec::new();
+        let mut port2 = Vec::new();
+        for chunk in chunks {
+            if chunk.port == 1 { chunk.inputs.iter().for_each(|byte| port1.push(*byte ^ 0xFF)) }
+            if chunk.port == 2 { chunk.inputs.iter().for_each(|byte| port2.push(*byte ^ 0xFF)) }
+        }
+        
+        // if they are different lengths, make up the difference with default inputs
+        let max_len = max(port1.len(), port2.len());
+        for _ in 0..(max_len - port1.len()) { port1.push(0xFF) }
+        for _ in 0..(max_len - port2.len()) { port2.push(0xFF) }
+        
+        let mut out = Vec::new();
+        for i in 0..max_len {
+            out.push(port1[i]);
+            out.push(port2[i]);
+        }
+        
+        Ok(out)
+    }
+}
+
+/// SNES input log with two controllers (.r16m).
+pub struct R16m;
+impl Importer for R16m {
+    fn metadata(&self) -> &'static FormatInfo {
+        &FormatInfo { name: "r16m", description: "SNES (.r16m)", extensions: &["r16m"] }
+    }
+    
+    fn console_types(&self) -> &'static [u8] { &[0x02] }
+    
+    fn import(&self, _path: &Path, data: &[u8], _console_type: u8) -> Result<Vec<Box<dyn Packet>>> {
+        let mut packets: Vec<Box<dyn Packet>> = Vec::new();
+        let mut result = data.to_vec();
+        if result.len() % 2 == 1 { result.push(0) } // Shouldn't ever be misaligned, but is safer to double check
+        if result.len() % 4 == 1 { result.push(0); result.push(0); } // Shouldn't ever be misaligned, but is safer to double check
+        packets.push(Box::new(ConsoleType::new(0x02, None)));
+        packets.push(Box::new(PortController::new(1, 0x0201)));
+        packets.push(Box::new(PortController::new(2, 0x0201)));
+        
+        let mut port1 = Vec::new();
+        let mut port2 = Vec::new();
+        for i in 0..(result.len() / 4) {
+            port1.push(result[i * 4] ^ 0xFF);
+            port1.push(result[(i * 4) + 1] ^ 0xFF);
+            port2.push(result[(i * 4) + 2] ^ 0xFF);
+            port2.push(result[(i * 4) + 3] ^ 0xFF);
+        }
+        packets.push(Box::new(InputChunk::new(1, port1)));
+        packets.push(Box::new(InputChunk::new(2, port2)));
+        
+        Ok(packets)
+    }
+}
+impl Exporter for R16m {
+    fn metadata(&self) -> &'static FormatInfo { Importer::metadata(self) }
+    
+    fn console_types(&self) -> &'static [u8] { &[0x02] }
+    
+    fn export(&self, tasd: &TasdMovie, _console_type: u8) -> Result<Vec<u8>> {
+        let chunks = tasd.search_by_key(vec![KEY_INPUT_CHUNK]);
+        let chunks: Vec<&InputChunk> = chunks.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>()).collect();
+        
+        let mut port1 = Vec::new();
+        let mut port2 = Vec::new();
+        for chunk in chunks {
+            if chunk.port == 1 { chunk.inputs.iter().for_each(|byte| port1.push(*byte ^ 0xFF)) }
+            if chunk.port == 2 { chunk.inputs.iter().for_each(|byte| port2.push(*byte ^ 0xFF)) }
+        }
+        
+        // if they are different lengths, make up the difference with default inputs
+        let max_len = max(port1.len(), port2.len());
+        for _ in 0..(max_len - port1.len()) { port1.push(0xFF) }
+        for _ in 0..(max_len - port2.len()) { port2.push(0xFF) }
+        
+        let mut out = Vec::new();
+        for i in 0..(max_len / 2) {
+            out.push(port1[i * 2]);
+            out.push(port1[(i * 2) + 1]);
+            out.push(port2[i * 2]);
+            out.push(port2[(i * 2) + 1]);
+        }
+        
+        Ok(out)
+    }
+}
+
+static GBI_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[0-9A-Fa-f]+ [0-9A-Fa-f]+\s*$").unwrap());
+
+/// GB, GBC, or GBA input log, with one line per input change (GBI .txt). The file doesn't record which handheld it's
+/// for.
+pub struct Gbi;
+impl Importer for Gbi {
+    fn metadata(&self) -> &'static FormatInfo {
+        &FormatInfo { name: "gbi", description: "GB/GBC/GBA (GBI .txt)", extensions: &["txt"] }
+    }
+    
+    fn console_types(&self) -> &'static [u8] { &[0x05, 0x06, 0x07] }
+    
+    /// Other text files share the extension, so the first line must also look like a GBI line.
+    fn detect(&self, path: &Path, data: &[u8]) -> bool {
+        let first = String::from_utf8_lossy(&data[..data.len().min(256)]).lines().find(|line| !line.trim().is_empty()).map(|line| GBI_LINE.is_match(line));
+        path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("txt")) && first.unwrap_or(true)
+    }
+    
+    fn import(&self, path: &Path, data: &[u8], console_type: u8) -> Result<Vec<Box<dyn Packet>>> {
+        if !Importer::console_types(self).contains(&console_type) { return Err(format!("GBI files can't be used for console type {:#04X}.", console_type).into()) }
+        let mut packets: Vec<Box<dyn Packet>> = Vec::new();
+        let result = std::str::from_utf8(data).map_err(|err| Error::Parse(format!("{}: {}", path.to_string_lossy(), err)))?;
+        packets.push(Box::new(ConsoleType::new(console_type, None)));
+        packets.push(Box::new(PortController::new(1, ((console_type as u16) << 8) | 0x01)));
+        
+        for (i, line) in result.lines().enumerate() {
+            if let Some(parts) = line.split_once(' ') {
+                let parse_err = |err| Error::Parse(format!("line {} of {}: {}", i + 1, path.to_string_lossy(), err));
+                let clock = u64::from_str_radix(parts.0.trim(), 16).map_err(parse_err)?;
+                let input = tasd::util::to_bytes(u16::from_str_radix(parts.1.trim(), 16).map_err(parse_err)? as usize, 2);
+                match console_type {
+                    0x07 => packets.push(Box::new(InputMoment::new(1, 0x02, clock, vec![input[0] ^ 0xFF, input[1] ^ 0xFF]))),
+                    _ => packets.push(Box::new(InputMoment::new(1, 0x02, clock, vec![input[1] ^ 0xFF]))),
+                }
+            }
+        }
+        
+        Ok(packets)
+    }
+}
+impl Exporter for Gbi {
+    fn metadata(&self) -> &'static FormatInfo { Importer::metadata(self) }
+    
+    fn console_types(&self) -> &'static [u8] { &[0x05, 0x06, 0x07] }
+    
+    fn export(&self, tasd: &TasdMovie, console_type: u8) -> Result<Vec<u8>> {
+        let moments = tasd.search_by_key(vec![KEY_INPUT_MOMENT]);
+        let moments: Vec<&InputMoment> = moments.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputMoment>()).collect();
+        
+        let mut out = Vec::new();
+        for moment in moments {
+            // GBA logs hold two bytes of input per line, and use Windows line endings
+            let line = if console_type == 0x07 {
+                let input: [u8; 2] = moment.inputs.as_slice().try_into().map_err(|_| Error::Other(format!("INPUT_MOMENT at index {} should have 2 bytes of input data.", moment.index)))?;
+                format!("{:08X} {:04X}\r\n", moment.index, u16::from_be_bytes(input) ^ 0xFFFF)
+            } else {
+                let input = moment.inputs.first().ok_or_else(|| Error::Other(format!("INPUT_MOMENT at index {} has no input data.", moment.index)))?;
+                format!("{:08X} {:04X}\n", moment.index, (input ^ 0xFF) as u16)
+            };
+            
+            line.as_bytes().iter().for_each(|byte| out.push(*byte));
+        }
+        
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    
+    fn round_trip<T: Importer + Exporter>(format: &T, name: &str, data: &[u8], console_type: u8) -> Vec<u8> {
+        let packets = format.import(Path::new(name), data, console_type).unwrap();
+        let tasd = TasdMovie { version: 1, keylen: 2, packets, source_path: PathBuf::from("movie.tasd") };
+        format.export(&tasd, console_type).unwrap()
+    }
+    
+    #[test]
+    fn input_logs_round_trip() {
+        let r08 = [0x01, 0x80, 0x00, 0xFF, 0x12, 0x34];
+        assert_eq!(round_trip(&R08, "movie.r08", &r08, 0x01), r08);
+        let r16m = [0x01, 0x02, 0x03, 0x04, 0xFF, 0x00, 0x80, 0x08];
+        assert_eq!(round_trip(&R16m, "movie.r16m", &r16m, 0x02), r16m);
+    }
+    
+    #[test]
+    fn imports_r08_inputs_per_port() {
+        let packets = R08.import(Path::new("movie.r08"), &[0x01, 0x80, 0x00, 0xFF], 0x01).unwrap();
+        let chunks: Vec<(u8, &[u8])> = packets.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>()).map(|chunk| (chunk.port, chunk.inputs.as_slice())).collect();
+        assert_eq!(chunks, [(1, &[0xFE, 0xFF][..]), (2, &[0x7F, 0x00][..])]);
+    }
+    
+    #[test]
+    fn gbi_logs_round_trip() {
+        let gb = b"00000010 0001\n0000A000 0080\n";
+        assert_eq!(round_trip(&Gbi, "movie.txt", gb, 0x05), gb);
+        // GBA logs hold two bytes per line and keep their Windows line endings
+        let gba = b"00000010 0301\r\n0000A000 0000\r\n";
+        assert_eq!(round_trip(&Gbi, "movie.txt", gba, 0x07), gba);
+    }
+    
+    #[test]
+    fn gbi_rejects_other_consoles_and_malformed_lines() {
+        assert!(Gbi.import(Path::new("movie.txt"), b"00000010 0001\n", 0x01).is_err());
+        assert!(Gbi.import(Path::new("movie.txt"), b"00000010 zz\n", 0x05).is_err());
+    }
+    
+    #[test]
+    fn detects_gbi_logs_by_content() {
+        assert!(Gbi.detect(Path::new("movie.txt"), b"\n00000010 0001\n"));
+        assert!(Gbi.detect(Path::new("movie.TXT"), b""));
+        assert!(!Gbi.detect(Path::new("notes.txt"), b"Some notes\n00000010 0001\n"));
+        assert!(!Gbi.detect(Path::new("movie.r08"), b"00000010 0001\n"));
+    }
+}
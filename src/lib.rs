@@ -0,0 +1,30 @@
+//! Reading, editing, and converting [TASD](https://github.com/ViGrey/TASD-File-Format) files.
+//!
+//! This is the library behind the `tasd-edit` tool. The conversions (legacy formats, JSON, and text), comparisons, and
+//! reports here work on a [`tasd::spec::TasdMovie`] or its raw bytes without prompting or printing, so other tools can
+//! use them directly.
+
+pub mod batch;
+pub mod config;
+pub mod convert;
+pub mod diff;
+pub mod error;
+pub mod fields;
+pub mod file;
+pub mod filter;
+pub mod input;
+pub mod inspect;
+pub mod json;
+pub mod legacy;
+pub mod provenance;
+pub mod raw;
+pub mod report;
+pub mod rom;
+pub mod stats;
+pub mod stored;
+pub mod style;
+pub mod template;
+pub mod text;
+pub mod timestamp;
+pub mod unknown;
+pub mod validate;
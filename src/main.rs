@@ -1,28 +1,413 @@
-use std::cmp::max;
 use std::ffi::OsStr;
-use std::io::{Error, stdout, Write};
-use std::path::PathBuf;
-use chrono::{Date, NaiveDate, NaiveTime, Utc};
-use clap::{App, AppSettings, Arg};
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind};
 use crossterm::execute;
 use crossterm::terminal::{SetTitle};
-use crossterm::style::Stylize;
+use crossterm::style::Color;
 use tasd::lookup::*;
 use tasd::spec::*;
+use tasd_edit::{batch, convert, diff, fields, file, filter, inspect, json, provenance, raw, report, rom, stats, style, text, timestamp, unknown, validate};
+use tasd_edit::error::{Error, Result};
+use tasd_edit::batch::BatchMode;
+use tasd_edit::config::{Config, Settings};
+use tasd_edit::convert::ConflictResolution;
+use tasd_edit::filter::PacketFilter;
+use tasd_edit::report::{PacketListing, ReportFormat};
+use tasd_edit::style::{ColorMode, Style};
+use tasd_edit::template::Template;
+use tasd_edit::unknown::UnknownPacket;
 
+mod pager;
 
-fn main() {
-    execute!(stdout(), SetTitle("TASD-Edit")).unwrap();
-    
-    let matches = App::new("TASD-Edit")
+/// Metadata template applied to every legacy file imported during this session.
+static TEMPLATE: Mutex<Option<Template>> = Mutex::new(None);
+/// Whether the open file has edits which haven't been saved, because auto-save is off.
+static UNSAVED: AtomicBool = AtomicBool::new(false);
+/// Settings in effect for this run: the config file, with any command line overrides. Set once at startup, and by the
+/// settings menu.
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings::new(Config::DEFAULT));
+/// How output is colored, following the `color` setting.
+static STYLE: Mutex<Style> = Mutex::new(Style::COLOR);
+
+fn settings() -> MutexGuard<'static, Settings> {
+    SETTINGS.lock().unwrap()
+}
+
+fn style() -> Style {
+    *STYLE.lock().unwrap()
+}
+
+fn cli() -> App<'static> {
+    App::new("TASD-Edit")
         .arg(Arg::new("path")
             .takes_value(true)
             .help("Path to file to open. Optional. May be .tasd or any supported legacy format."))
+        .arg(Arg::new("color")
+            .long("color")
+            .takes_value(true)
+            .possible_values(["auto", "always", "never"])
+            .global(true)
+            .help("When to use colored output. 'auto' disables colors if stdout isn't a terminal or NO_COLOR is set. Defaults to the config file's setting, or 'auto'."))
+        .arg(Arg::new("export-dir")
+            .long("export-dir")
+            .takes_value(true)
+            .global(true)
+            .help("Directory to write exported legacy files to, instead of next to the TASD file. Overrides the config file."))
+        .arg(Arg::new("auto-save")
+            .long("auto-save")
+            .takes_value(true)
+            .possible_values(["true", "false"])
+            .global(true)
+            .help("Whether edits made from the menus are saved right away. When false, use 'Save changes' from the main menu. Overrides the config file."))
+        .arg(Arg::new("no-last-modified")
+            .long("no-last-modified")
+            .global(true)
+            .help("Don't insert or update the DUMP_LAST_MODIFIED packet when saving, so that saving the same edits always produces identical files."))
+        .arg(Arg::new("tasd-version")
+            .long("tasd-version")
+            .takes_value(true)
+            .global(true)
+            .help("TASD version given to newly created files, e.g. 0x0001. Defaults to the latest version supported."))
+        .arg(Arg::new("key-width")
+            .long("key-width")
+            .takes_value(true)
+            .global(true)
+            .help("Key width in bytes given to newly created files. Defaults to 2."))
+        .arg(Arg::new("template")
+            .long("template")
+            .takes_value(true)
+            .global(true)
+            .help("Metadata template applied to every imported legacy file. Either a .toml file listing packets, or a .tasd file whose metadata packets (e.g. attribution, licensing, comments) are copied."))
+        .subcommand(App::new("diff")
+            .about("Compares two TASD files frame-by-frame and reports where they diverge.")
+            .arg(Arg::new("left")
+                .required(true)
+                .help("Path to the first .tasd file."))
+            .arg(Arg::new("right")
+                .required(true)
+                .help("Path to the second .tasd file."))
+            .arg(Arg::new("json")
+                .long("json")
+                .help("Print the result as JSON instead of a human-readable summary."))
+            .arg(Arg::new("include-volatile")
+                .long("include-volatile")
+                .help("Also compare DUMP_CREATED, DUMP_LAST_MODIFIED, and the comments TASD-Edit adds when creating or importing a file. These differ between any two dumps, so they're skipped by default.")))
+        .subcommand(App::new("info")
+            .alias("dump")
+            .about("Prints the packets of a TASD file, optionally filtered.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file."))
+            .arg(Arg::new("key")
+                .long("key")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .help("Only show packets of this type (e.g. GAME_TITLE, or a key like 0x0101). May be repeated or comma-separated."))
+            .arg(Arg::new("exclude-inputs")
+                .long("exclude-inputs")
+                .help("Hide INPUT_CHUNK and INPUT_MOMENT packets."))
+            .arg(Arg::new("port")
+                .long("port")
+                .takes_value(true)
+                .help("Only show packets for this controller port."))
+            .arg(Arg::new("frames")
+                .long("frames")
+                .takes_value(true)
+                .help("Only show frame-indexed packets covering this range, e.g. 100-200, 100-, or 100."))
+            .arg(Arg::new("grep")
+                .long("grep")
+                .takes_value(true)
+                .help("Only show packets with a text field matching this regular expression.")))
+        .subcommand(App::new("stats")
+            .about("Reports input statistics, lag frames, transitions, and the expected real-time duration of a TASD file.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file.")))
+        .subcommand(App::new("validate")
+            .about("Lists problems with a TASD file, such as unknown packets or missing console information. Exits with 1 if any are found.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file.")))
+        .subcommand(App::new("inspect")
+            .about("Shows the exact bytes of a single packet: its file offset, key, length encoding, fields, and a hex dump.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file."))
+            .arg(Arg::new("index")
+                .required(true)
+                .help("Index of the packet, as shown when listing packets."))
+            .arg(Arg::new("extract")
+                .long("extract")
+                .takes_value(true)
+                .min_values(0)
+                .help("Write the payload of an UNSPECIFIED, MOVIE_FILE, or MEMORY_INIT packet to a file. Defaults to a name based on the packet, next to the input file.")))
+        .subcommand(App::new("import")
+            .about("Converts a legacy input file into a new TASD file.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the legacy file."))
+            .arg(Arg::new("output")
+                .help("Path to write the TASD file to. Defaults to the input path with a .tasd extension."))
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(convert::IMPORTERS.iter().map(|importer| importer.metadata().name))
+                .help("Format of the input file. Detected from the file if not given."))
+            .arg(Arg::new("console")
+                .long("console")
+                .takes_value(true)
+                .help("Console the input file is for (e.g. GBA, or a console type like 0x07). Required for formats which are used by more than one console.")))
+        .subcommand(App::new("export")
+            .about("Converts the inputs of a TASD file into a legacy input file.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file."))
+            .arg(Arg::new("output")
+                .help("Path to write the legacy file to. Defaults to the input path with an .export.<format> extension."))
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(convert::EXPORTERS.iter().map(|exporter| exporter.metadata().name))
+                .help("Format to export to. Chosen from the file's CONSOLE_TYPE packets if not given."))
+            .arg(Arg::new("console")
+                .long("console")
+                .takes_value(true)
+                .help("Console to export the inputs of (e.g. NES, or a console type like 0x01). Required if the file has more than one exportable CONSOLE_TYPE packet.")))
+        .subcommand(App::new("batch")
+            .about("Converts many files at once, in parallel. Legacy files are imported into TASD files, or with --export, TASD files are exported to legacy files.")
+            .arg(Arg::new("inputs")
+                .required(true)
+                .multiple_values(true)
+                .help("Files, directories, or glob patterns such as \"movies/*.r08\". Directories include every file with a supported extension."))
+            .arg(Arg::new("export")
+                .long("export")
+                .help("Export .tasd files to legacy files instead of importing legacy files."))
+            .arg(Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(convert::IMPORTERS.iter().map(|importer| importer.metadata().name).chain(convert::EXPORTERS.iter().map(|exporter| exporter.metadata().name)).collect::<std::collections::BTreeSet<_>>())
+                .help("Format to import from or export to. Chosen for each file if not given."))
+            .arg(Arg::new("console")
+                .long("console")
+                .takes_value(true)
+                .help("Console type to use for formats which don't record one (e.g. GBA), or to export when a file has more than one."))
+            .arg(Arg::new("out-dir")
+                .long("out-dir")
+                .takes_value(true)
+                .help("Directory to write converted files to. Defaults to next to each input."))
+            .arg(Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .takes_value(true)
+                .help("Number of files to convert at the same time. Defaults to the number of CPUs."))
+            .arg(Arg::new("report")
+                .long("report")
+                .takes_value(true)
+                .help("Also write the summary of successes and failures to this file.")))
+        .subcommand(App::new("to-json")
+            .about("Converts a TASD file into a lossless JSON representation.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file."))
+            .arg(Arg::new("output")
+                .help("Path to write the JSON to. Defaults to the input path with a .json extension.")))
+        .subcommand(App::new("from-json")
+            .about("Converts JSON created by to-json back into a TASD file.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .json file."))
+            .arg(Arg::new("output")
+                .help("Path to write the TASD file to. Defaults to the input path with a .tasd extension.")))
+        .subcommand(App::new("to-text")
+            .about("Converts a TASD file into an editable text format, with one packet per line and inputs as button mnemonics.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the .tasd file."))
+            .arg(Arg::new("output")
+                .help("Path to write the text to. Defaults to the input path with a .tasd.txt extension.")))
+        .subcommand(App::new("from-text")
+            .about("Converts text created by to-text (or written by hand) back into a TASD file.")
+            .arg(Arg::new("input")
+                .required(true)
+                .help("Path to the text file."))
+            .arg(Arg::new("output")
+                .help("Path to write the TASD file to. Defaults to the input path with a .tasd extension.")))
         .setting(AppSettings::DeriveDisplayOrder)
         .setting(AppSettings::NextLineHelp)
-        .get_matches();
+}
+
+fn main() {
+    let matches = cli().get_matches();
+    if matches.is_present("path") && matches.subcommand().is_some() {
+        cli().error(ErrorKind::ArgumentConflict, "A file to open can't be given along with a subcommand").exit();
+    }
+    
+    let mut config = Config::load().unwrap_or_else(|err| { println!("Err: {}\nUsing default settings.", err); Config::DEFAULT });
+    if let Some(color) = matches.value_of("color") { config.color = Some(color.parse().unwrap()) }
+    if let Some(dir) = matches.value_of("export-dir") { config.export_dir = Some(PathBuf::from(dir)) }
+    if let Some(auto_save) = matches.value_of("auto-save") { config.auto_save = auto_save == "true" }
+    *STYLE.lock().unwrap() = Style::new(config.color.unwrap_or(ColorMode::Auto));
+    let mut startup = Settings::new(config);
+    startup.update_last_modified = !matches.is_present("no-last-modified");
+    if let Some(version) = matches.value_of("tasd-version") {
+        match parse_version(version) {
+            Ok(version) => startup.new_file_header.version = version,
+            Err(err) => { println!("Err: {}", err); exit(false, 2) },
+        }
+    }
+    if let Some(keylen) = matches.value_of("key-width") {
+        match parse_keylen(keylen) {
+            Ok(keylen) => startup.new_file_header.keylen = keylen,
+            Err(err) => { println!("Err: {}", err); exit(false, 2) },
+        }
+    }
+    *SETTINGS.lock().unwrap() = startup;
+    if let Some(path) = matches.value_of("template") {
+        match Template::load(Path::new(path)) {
+            Ok(template) => *TEMPLATE.lock().unwrap() = Some(template),
+            Err(err) => { println!("Err: {}: {}", path, err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("diff") {
+        let left = open_existing_tasd(matches.value_of("left").unwrap());
+        let right = open_existing_tasd(matches.value_of("right").unwrap());
+        let (left, right) = match (left, right) {
+            (Ok(left), Ok(right)) => (left, right),
+            (Err(err), _) | (_, Err(err)) => { println!("Err: {}", err); exit(false, 2) },
+        };
+        
+        let diff = diff::diff_movies(&left, &right, matches.is_present("include-volatile"));
+        if matches.is_present("json") {
+            println!("{}", serde_json::to_string_pretty(&diff.to_json()).unwrap());
+        } else {
+            diff.pretty(style()).iter().for_each(|line| println!("{}", line));
+        }
+        exit(false, if diff.is_identical() { 0 } else { 1 });
+    }
     
-    println!("");
+    if let Some(matches) = matches.subcommand_matches("info") {
+        let result = open_existing_tasd(matches.value_of("input").unwrap()).and_then(|tasd| {
+            let mut filter = PacketFilter::default();
+            for keys in matches.values_of("key").into_iter().flatten() {
+                filter.keys.extend(filter::parse_keys(keys)?);
+            }
+            if matches.is_present("exclude-inputs") { filter.exclude_keys = vec![KEY_INPUT_CHUNK, KEY_INPUT_MOMENT] }
+            if let Some(port) = matches.value_of("port") { filter.port = Some(port.parse()?) }
+            if let Some(frames) = matches.value_of("frames") { filter.frames = Some(filter::parse_range(frames)?) }
+            if let Some(pattern) = matches.value_of("grep") { filter.pattern = Some(regex::Regex::new(pattern).map_err(|err| Error::Parse(err.to_string()))?) }
+            
+            display_packets(&tasd, &filter);
+            Ok(())
+        });
+        match result {
+            Ok(()) => exit(false, 0),
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("stats") {
+        match open_existing_tasd(matches.value_of("input").unwrap()) {
+            Ok(tasd) => { stats::movie_stats(&tasd).pretty().iter().for_each(|line| println!("{}", line)); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("validate") {
+        match open_existing_tasd(matches.value_of("input").unwrap()) {
+            Ok(tasd) => exit(false, if print_issues(&tasd) { 0 } else { 1 }),
+            Err(err) => { println!("Err: {}", err); exit(false, 2) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("inspect") {
+        match inspect_command(matches) {
+            Ok(()) => exit(false, 0),
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("import") {
+        match import_command(matches) {
+            Ok(output) => { println!("TASD file has been written to: {}", output.to_string_lossy()); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("export") {
+        match export_command(matches) {
+            Ok(output) => { println!("Legacy file data has been exported to: {}", output.to_string_lossy()); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("batch") {
+        match batch_command(matches) {
+            Ok(true) => exit(false, 0),
+            Ok(false) => exit(false, 1),
+            Err(err) => { println!("Err: {}", err); exit(false, 2) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("to-json") {
+        let input = PathBuf::from(matches.value_of("input").unwrap());
+        let output = matches.value_of("output").map(PathBuf::from).unwrap_or_else(|| input.with_extension("json"));
+        let result = std::fs::read(&input).map_err(Error::from)
+            .and_then(|data| json::to_json(&data))
+            .and_then(|json| Ok(std::fs::write(&output, serde_json::to_string_pretty(&json).unwrap())?));
+        match result {
+            Ok(()) => { println!("JSON has been written to: {}", output.to_string_lossy()); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("from-json") {
+        let input = PathBuf::from(matches.value_of("input").unwrap());
+        let output = matches.value_of("output").map(PathBuf::from).unwrap_or_else(|| input.with_extension("tasd"));
+        let result = std::fs::read_to_string(&input).map_err(Error::from)
+            .and_then(|text| serde_json::from_str(&text).map_err(|err| Error::Parse(err.to_string())))
+            .and_then(|json| json::from_json(&json))
+            .and_then(|data| Ok(std::fs::write(&output, data)?));
+        match result {
+            Ok(()) => { println!("TASD file has been written to: {}", output.to_string_lossy()); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("to-text") {
+        let input = PathBuf::from(matches.value_of("input").unwrap());
+        let output = matches.value_of("output").map(PathBuf::from).unwrap_or_else(|| input.with_extension("tasd.txt"));
+        let result = std::fs::read(&input).map_err(Error::from)
+            .and_then(|data| text::to_text(&data))
+            .and_then(|text| Ok(std::fs::write(&output, text)?));
+        match result {
+            Ok(()) => { println!("Text has been written to: {}", output.to_string_lossy()); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if let Some(matches) = matches.subcommand_matches("from-text") {
+        let input = PathBuf::from(matches.value_of("input").unwrap());
+        let output = matches.value_of("output").map(PathBuf::from).unwrap_or_else(|| {
+            let name = input.to_string_lossy();
+            PathBuf::from(name.strip_suffix(".txt").filter(|name| name.ends_with(".tasd")).map(String::from).unwrap_or_else(|| input.with_extension("tasd").to_string_lossy().to_string()))
+        });
+        let result = std::fs::read_to_string(&input).map_err(Error::from)
+            .and_then(|text| text::from_text(&text))
+            .and_then(|data| Ok(std::fs::write(&output, data)?));
+        match result {
+            Ok(()) => { println!("TASD file has been written to: {}", output.to_string_lossy()); exit(false, 0) },
+            Err(err) => { println!("Err: {}", err); exit(false, 1) },
+        }
+    }
+    
+    if style::stdout_is_terminal() {
+        execute!(stdout(), SetTitle("TASD-Edit")).unwrap();
+    }
+    println!();
     
     let mut tasd = None;
     
@@ -34,29 +419,38 @@ fn main() {
         }
         
         if path.is_file() {
-            let data = std::fs::read(&path).unwrap();
+            let data = match std::fs::read(&path) {
+                Ok(data) => data,
+                Err(err) => { println!("Err: {}", err); exit(true, 1); }
+            };
             let ext = path.extension().unwrap_or(OsStr::new("")).to_string_lossy();
-            if data[0..4] == MAGIC_NUMBER {
+            if data.starts_with(&MAGIC_NUMBER) {
                 if ext != "tasd" {
                     println!("Warning: File extension should be .tasd for all TASD files.");
                 }
-                tasd = Some(TasdMovie::new(&path).unwrap());
-            } else { match ext.as_ref() {
-                "r08" | "r16m" | "txt" => match import_legacy(&mut tasd, Some(&path)) {
-                    Ok(()) => (),
-                    Err(err) => { println!("Err: {}", err); exit(true, 0); }
-                },
-                _ => { println!("Unable to determine what kind of file this is. Make sure it is a TASD file or has a supported legacy extention (.r08, .r16m, .txt)."); }
-            }}
+                match file::load(&path) {
+                    Ok(x) => { warn_version(&x); tasd = Some(x) },
+                    Err(err) => println!("Err: {}\n", err),
+                }
+            } else if convert::detect_importer(&path, &data).is_some() {
+                if let Err(err) = import_legacy(&mut tasd, Some(&path)) {
+                    println!("Err: {}\n", err);
+                }
+            } else {
+                println!("Unable to determine what kind of file this is. Make sure it is a TASD file or has a supported legacy extention ({}).", convert::import_extensions());
+            }
+        } else if convert::importer_for_extension(&path).is_some() {
+            if let Err(err) = import_legacy(&mut tasd, Some(&path)) {
+                println!("Err: {}\n", err);
+            }
         } else {
-            match path.extension().unwrap_or(OsStr::new("")).to_string_lossy().as_ref() {
-                "r08" | "r16m" | "txt" => match import_legacy(&mut tasd, Some(&path)) {
-                    Ok(()) => (),
-                    Err(err) => { println!("Err: {}", err); exit(true, 0); }
+            match file::load_or_create(&path, &settings()) {
+                Ok((x, created)) => {
+                    if created { println!("Created new file: {}\n", path.to_string_lossy()) }
+                    warn_version(&x);
+                    tasd = Some(x);
                 },
-                _ => {
-                    tasd = Some(TasdMovie::new(&path).unwrap());
-                }
+                Err(err) => println!("Err: {}\n", err),
             }
         }
     }
@@ -76,33 +470,69 @@ fn main_menu(tasd_option: &mut Option<TasdMovie>) -> bool {
                 "Import data from TASVideos",
                 "Display all packets",
                 "Display all, except inputs",
-                //"Save prettified packets to file",
+                "Save prettified packets to file",
                 "Create/load a different TASD file",
                 "Import and append a legacy file",
                 "Export to legacy file",
+                "Restore from backup",
+                "Fill in metadata from a ROM header",
+                "Show statistics",
+                "Search/filter packets",
+                "Apply a metadata template",
+                "Save changes",
+                "Settings",
+                "Change key width",
+                "Validate file",
+                "Export/remove unknown packets",
+                "Inspect packet",
             ], Some("What would you like to do?\n"), Some("Option[0]: ")
         );
         
         let mut ret = false;
         match selection {
-          //0 => exits program
+            0 => ret = offer_save(tasd),
             1 => { while !add_menu(tasd) {} },
             2 => { while !remove_menu(tasd) {} },
-            3 => { import_tasvideos(tasd); }
-            4 => { display_packets(tasd, false); },
-            5 => { display_packets(tasd, true); },
-            //6 => { save_pretty(tasd); }, //TODO: Sanitize ANSI color symbols 
-            6 => { match load_tasd() {
-                Err(x) => println!("Err: {:?}\n", x),
+            3 => { if let Err(x) = import_tasvideos(tasd) { println!("Err: {}\n", x); }},
+            4 => { display_packets(tasd, &PacketFilter::default()); },
+            5 => { display_packets(tasd, &PacketFilter { exclude_keys: vec![KEY_INPUT_CHUNK, KEY_INPUT_MOMENT], ..Default::default() }); },
+            6 => { save_pretty(tasd); },
+            7 => { if offer_save(tasd) { match load_tasd() {
+                Err(x) => println!("Err: {}\n", x),
                 Ok(x) => *tasd = x,
+            }}},
+            8 => { if let Err(x) = import_legacy(tasd_option, None) {
+                println!("Err: {}\n", x);
+            }},
+            9 => { if let Err(x) = export_legacy(tasd) {
+                println!("Err: {}\n", x);
+            }},
+            10 => { if let Err(x) = restore_menu(tasd) {
+                println!("Err: {}\n", x);
+            }},
+            11 => { if let Err(x) = autofill_menu(tasd) {
+                println!("Err: {}\n", x);
             }},
-            7 => { match import_legacy(tasd_option, None) {
+            12 => {
+                stats::movie_stats(tasd).pretty().iter().for_each(|line| println!("{}", line));
+                println!();
+            },
+            13 => { match filter_menu() {
+                Ok(filter) => display_packets(tasd, &filter),
+                Err(x) => println!("Err: {}\n", x),
+            }},
+            14 => { if let Err(x) = template_menu(tasd) { println!("Err: {}\n", x); }},
+            15 => { match file::save(tasd, &settings()) {
+                Ok(()) => { UNSAVED.store(false, Ordering::Relaxed); println!("File saved.\n"); },
                 Err(x) => println!("Err: {}\n", x),
-                Ok(_) => ()
             }},
-            8 => { export_legacy(tasd) },
+            16 => { if let Err(x) = settings_menu(tasd) { println!("Err: {}\n", x); }},
+            17 => { if let Err(x) = keylen_menu(tasd) { println!("Err: {}\n", x); }},
+            18 => { print_issues(tasd); println!(); },
+            19 => { if let Err(x) = unknown_menu(tasd) { println!("Err: {}\n", x); }},
+            20 => { if let Err(x) = inspect_menu(tasd) { println!("Err: {}\n", x); }},
             
-            _ => ret = true,
+            _ => ret = offer_save(tasd),
         };
         
         ret
@@ -118,13 +548,12 @@ fn main_menu(tasd_option: &mut Option<TasdMovie>) -> bool {
         match selection {
           //0 => exits program
             1 => { match load_tasd() {
-                Err(x) => println!("Err: {:?}\n", x),
+                Err(x) => println!("Err: {}\n", x),
                 Ok(x) => *tasd_option = Some(x),
             }},
             2 => {
-                match import_legacy(tasd_option, None) {
-                    Err(x) => println!("Err: {}\n", x),
-                    Ok(_) => ()
+                if let Err(x) = import_legacy(tasd_option, None) {
+                    println!("Err: {}\n", x);
                 }
             },
             
@@ -138,18 +567,19 @@ fn main_menu(tasd_option: &mut Option<TasdMovie>) -> bool {
 fn add_menu(tasd: &mut TasdMovie) -> bool {
     let create = create_packet(Some("Select the packet you'd like to add.\n"), Some(vec![KEY_DUMP_LAST_MODIFIED]));
     
-    if let Some(packet) = create.1 {
-        if packet.key() != [0x00, 0x00] { // if packet is supported...
-            tasd.packets.push(packet);
-            tasd.save().unwrap();
-            println!("New packet added to file!\n");
+    let count = create.1.len();
+    if count > 0 {
+        tasd.packets.extend(create.1);
+        match save_edits(tasd) {
+            Ok(()) => println!("{} added to file!\n", if count == 1 { "New packet".to_owned() } else { format!("{} new packets", count) }),
+            Err(err) => println!("Err: {}\n", err),
         }
     }
     
     create.0
 }
 
-fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool, Option<Box<dyn Packet>>){
+fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool, Vec<Box<dyn Packet>>){
     let exclude = exclude.unwrap_or(vec![]);
     let mut options = vec!["Return to add menu".to_owned()];
     
@@ -157,11 +587,11 @@ fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool,
     for (key, name, description) in get_keys() {
         if !exclude.contains(&key) {
             included_types.push((key, name, description));
-            options.push(format!("{}: {}", name.dark_yellow(), description));
+            options.push(format!("{}: {}", style().paint(name, Color::DarkYellow), description));
         }
     }
     let selection = cli_selection(&options.iter().map(|s| s.as_ref()).collect::<Vec<&str>>(), pretext, Some("Packet Type[0]: "));
-    if selection == 0 { return (true, None); }
+    if selection == 0 { return (true, vec![]); }
     
     let spec = &included_types[selection - 1];
     let packet: Box<dyn Packet> = match spec.0 {
@@ -172,12 +602,12 @@ fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool,
                 if let Some(kind) = console_type_lut(i) { options.push(kind); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Console Type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let kind = kinds[selection - 1];
             
             if kind == 0xFF {
                 let text = cli_read(Some("Custom type: "));
-                if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+                if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
                 
                 Box::new(ConsoleType::new(kind, Some(text.unwrap())))
             } else {
@@ -188,21 +618,20 @@ fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool,
             let mut options = vec!["Return to add menu"];
             let mut kinds = Vec::new();
             for i in 1..=255 {
-                let s = console_region_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = console_region_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Console Region[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             Box::new(ConsoleRegion::new(kinds[selection - 1]))
         },
         KEY_GAME_TITLE => {
             let text = cli_read(Some("Game title: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(GameTitle::new(text.unwrap()))
         },
         KEY_ROM_NAME => {
             let text = cli_read(Some("ROM filename: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(RomName::new(text.unwrap()))
         }
         KEY_ATTRIBUTION => {
@@ -212,106 +641,79 @@ fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool,
                 if let Some(kind) = attribution_lut(i) { options.push(kind); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Attribution Type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let kind = kinds[selection - 1];
             
-            let text = cli_read(Some("Name: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
-            Box::new(Attribution::new(kind, text.unwrap()))
+            let default = settings().config.attribution.clone();
+            let text = cli_read(Some(&match &default { Some(name) => format!("Name [{}]: ", name), None => "Name: ".to_owned() }));
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
+            let text = text.unwrap();
+            Box::new(Attribution::new(kind, if text.is_empty() { default.unwrap_or(text) } else { text }))
         },
         KEY_CATEGORY => {
             let text = cli_read(Some("Category: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(Category::new(text.unwrap()))
         },
         KEY_EMULATOR_NAME => {
             let text = cli_read(Some("Emulator name: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(EmulatorName::new(text.unwrap()))
         },
         KEY_EMULATOR_VERSION => {
             let text = cli_read(Some("Emulator version: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(EmulatorVersion::new(text.unwrap()))
         },
         KEY_EMULATOR_CORE => {
             let text = cli_read(Some("Emulator core: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(EmulatorCore::new(text.unwrap()))
         },
-        KEY_TAS_LAST_MODIFIED => {
-            let text = cli_read(Some("TAS last modified (epoch seconds, YYYY-MM-DD, or YYYY-MM-DD HH:MM:SS): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
-            let text = text.unwrap();
-            
-            let epoch;
-            let mut parse_attempt = NaiveDate::parse_from_str(&text, "%Y-%m-%d %H:%M:%S");
-            if parse_attempt.is_err() {
-                parse_attempt = NaiveDate::parse_from_str(&text, "%Y-%m-%d");
-            }
-            if parse_attempt.is_ok() {
-                let parsed = parse_attempt.unwrap();
-                let date = Date::<Utc>::from_utc(parsed, Utc);
-                epoch = date.and_time(NaiveTime::from_hms(0,0,0)).unwrap().timestamp();
-            } else {
-                let parse_attempt = text.parse();
-                if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
-                epoch = parse_attempt.unwrap();
-            }
-            Box::new(TasLastModified::new(epoch))
-        },
-        KEY_DUMP_CREATED => {
-            let text = cli_read(Some("Dump created (epoch seconds, YYYY-MM-DD, or YYYY-MM-DD HH:MM:SS): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
-            let text = text.unwrap();
+        KEY_TAS_LAST_MODIFIED | KEY_DUMP_CREATED => {
+            let name = if spec.0 == KEY_TAS_LAST_MODIFIED { "TAS last modified" } else { "Dump created" };
+            let text = cli_read(Some(&format!("{} ({}): ", name, timestamp::FORMATS_HELP)));
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
+            let epoch = timestamp::parse(&text.unwrap());
+            if epoch.is_err() { println!("Err: {}\n", epoch.err().unwrap()); return (false, vec![]); }
             
-            let epoch;
-            let mut parse_attempt = NaiveDate::parse_from_str(&text, "%Y-%m-%d %H:%M:%S");
-            if parse_attempt.is_err() {
-                parse_attempt = NaiveDate::parse_from_str(&text, "%Y-%m-%d");
-            }
-            if parse_attempt.is_ok() {
-                let parsed = parse_attempt.unwrap();
-                let date = Date::<Utc>::from_utc(parsed, Utc);
-                epoch = date.and_time(NaiveTime::from_hms(0,0,0)).unwrap().timestamp();
+            if spec.0 == KEY_TAS_LAST_MODIFIED {
+                Box::new(TasLastModified::new(epoch.unwrap()))
             } else {
-                let parse_attempt = text.parse();
-                if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
-                epoch = parse_attempt.unwrap();
+                Box::new(DumpCreated::new(epoch.unwrap()))
             }
-            Box::new(DumpCreated::new(epoch))
         },
         KEY_TOTAL_FRAMES => {
             let text = cli_read(Some("Total frames: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(TotalFrames::new(parse_attempt.unwrap()))
         },
         KEY_RERECORDS => {
             let text = cli_read(Some("Rerecord count: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(Rerecords::new(parse_attempt.unwrap()))
         },
         KEY_SOURCE_LINK => {
             let text = cli_read(Some("Source link/url: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(SourceLink::new(text.unwrap()))
         },
         KEY_BLANK_FRAMES => {
             let text = cli_read(Some("Blank frames (-32768 to +32767): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(BlankFrames::new(parse_attempt.unwrap()))
         },
         KEY_VERIFIED => {
             let text = cli_read(Some("Has been verified (true or false): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse::<bool>();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(Verified::new(parse_attempt.unwrap()))
         },
         KEY_MEMORY_INIT => {
@@ -319,156 +721,198 @@ fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool,
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=255 {
-                let s = memory_init_data_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = memory_init_data_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Initialization type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let data_kind = kinds[selection - 1];
             
             let mut options = Vec::new();
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=65535 {
-                let s = memory_init_device_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = memory_init_device_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Initialization type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let device_kind = kinds[selection - 1];
             
             let name = cli_read(Some("Name of memory space: "));
-            if name.is_err() { println!("Err: {:?}\n", name.err().unwrap()); return (false, None); }
+            if name.is_err() { println!("Err: {:?}\n", name.err().unwrap()); return (false, vec![]); }
             
             let mut payload = None;
             if data_kind == 0xFF {
                 let path = cli_read(Some("Path to file containing memory data: "));
-                if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, None); }
+                if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, vec![]); }
                 let path = PathBuf::from(path.unwrap());
-                if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, None); }
+                if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, vec![]); }
                 let data_result = std::fs::read(path);
-                if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, None); }
+                if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, vec![]); }
                 payload = Some(data_result.unwrap());
             }
             
             let required = cli_read(Some("Required for verification (true or false): "));
-            if required.is_err() { println!("Err: {:?}\n", required.err().unwrap()); return (false, None); }
+            if required.is_err() { println!("Err: {:?}\n", required.err().unwrap()); return (false, vec![]); }
             let required = required.unwrap().parse::<bool>();
-            if required.is_err() { println!("Err: {:?}\n", required.err().unwrap()); return (false, None); }
+            if required.is_err() { println!("Err: {:?}\n", required.err().unwrap()); return (false, vec![]); }
             
             Box::new(MemoryInit::new(data_kind, device_kind, required.unwrap(), name.unwrap(), payload))
         },
         KEY_GAME_IDENTIFIER => {
+            let source = cli_selection(&["Return to add menu", "Compute hashes of a ROM file", "Enter an identifier manually"], None, Some("Identifier source[0]: "));
+            if source == 0 { return (false, vec![]); }
+            
+            if source == 1 {
+                let path = cli_read(Some("Path to ROM file: "));
+                if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, vec![]); }
+                let path = PathBuf::from(path.unwrap());
+                if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, vec![]); }
+                let data_result = std::fs::read(&path);
+                if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, vec![]); }
+                let data = data_result.unwrap();
+                
+                let mut options = vec!["Return to add menu", "All of the below"];
+                for kind in rom::HASH_KINDS {
+                    options.push(game_identifier_lut(kind).unwrap_or("Unknown"));
+                }
+                let selection = cli_selection(&options, None, Some("Identifier type[0]: "));
+                if selection == 0 { return (false, vec![]); }
+                let kinds = if selection == 1 { rom::HASH_KINDS.to_vec() } else { vec![rom::HASH_KINDS[selection - 2]] };
+                
+                let (rom, header) = rom::strip_header(&path, &data);
+                if let Some(header) = header { println!("The {} was excluded from the hashes.\n", header); }
+                
+                return (false, kinds.into_iter().filter_map(|kind| rom::hash(kind, rom).map(|hash| Box::new(GameIdentifier::new(kind, 0x01, hash)) as Box<dyn Packet>)).collect());
+            }
+            
             let mut options = Vec::new();
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=0xFF {
-                let s = game_identifier_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = game_identifier_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Identifier type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let kind = kinds[selection - 1];
             
-            let identifier = cli_read(Some("Identifier (represented in base16 hexadecimal): "));
-            if identifier.is_err() { println!("Err: {:?}\n", identifier.err().unwrap()); return (false, None); }
-            let mut identifier = identifier.unwrap();
-            identifier = identifier.replace(" ", "");
-            let identifier = (0..identifier.len()).step_by(2).map(|i| u8::from_str_radix(&identifier[i..i + 2], 16).unwrap()).collect();
+            let mut options = Vec::new();
+            let mut encodings = Vec::new();
+            options.push("Return to add menu");
+            for i in 1..=0xFF {
+                if let Some(s) = identifier_encoding_lut(i) { options.push(s); encodings.push(i); }
+            }
+            let selection = cli_selection(&options, None, Some("Identifier encoding[0]: "));
+            if selection == 0 { return (false, vec![]); }
+            let encoding = encodings[selection - 1];
             
-            Box::new(GameIdentifier::new(kind, 0x02, identifier))
+            if encoding == 0x01 {
+                let identifier = cli_read(Some("Identifier (represented in base16 hexadecimal, stored as raw bytes): "));
+                if identifier.is_err() { println!("Err: {:?}\n", identifier.err().unwrap()); return (false, vec![]); }
+                let identifier = fields::decode_hex(&identifier.unwrap());
+                if identifier.is_err() { println!("Err: {}\n", identifier.err().unwrap().context("Identifier")); return (false, vec![]); }
+                
+                Box::new(GameIdentifier::new(kind, encoding, identifier.unwrap()))
+            } else {
+                let identifier = cli_read(Some("Identifier (stored exactly as typed): "));
+                if identifier.is_err() { println!("Err: {:?}\n", identifier.err().unwrap()); return (false, vec![]); }
+                
+                Box::new(GameIdentifier::new(kind, encoding, identifier.unwrap().into_bytes()))
+            }
         },
         KEY_MOVIE_LICENSE => {
             let text = cli_read(Some("Movie license: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(MovieLicense::new(text.unwrap()))
         },
         KEY_MOVIE_FILE => {
             let path = cli_read(Some("Path to movie file: "));
-            if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, None); }
+            if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, vec![]); }
             let path = PathBuf::from(path.unwrap());
-            if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, None); }
+            if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, vec![]); }
             let data_result = std::fs::read(path.clone());
-            if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, None); }
+            if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, vec![]); }
             Box::new(MovieFile::new(path.file_name().unwrap().to_string_lossy().to_string(), data_result.unwrap()))
         },
         KEY_PORT_CONTROLLER => {
             let text = cli_read(Some("Port number (1-indexed): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             let port = parse_attempt.unwrap();
             
             let mut options = Vec::new();
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=0xFFFF {
-                let s = controller_type_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = controller_type_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Controller type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let kind = kinds[selection - 1];
             
             Box::new(PortController::new(port, kind))
         },
         
         KEY_NES_LATCH_FILTER => {
-            let text = cli_read(Some("Latch filter (integer from 0-65535; which will be multiplied by 1.0us): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
-            let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            let default = settings().config.nes_latch_filter;
+            let text = cli_read(Some(&format!("Latch filter (integer from 0-65535; which will be multiplied by 1.0us){}: ", default.map(|time| format!(" [{}]", time)).unwrap_or_default())));
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
+            let text = text.unwrap();
+            let parse_attempt = match default { Some(time) if text.is_empty() => Ok(time), _ => text.parse() };
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(NesLatchFilter::new(parse_attempt.unwrap()))
         },
         KEY_NES_CLOCK_FILTER => {
-            let text = cli_read(Some("Clock filter (integer from 0-255; which will be multiplied by 0.1us): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
-            let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            let default = settings().config.nes_clock_filter;
+            let text = cli_read(Some(&format!("Clock filter (integer from 0-255; which will be multiplied by 0.1us){}: ", default.map(|time| format!(" [{}]", time)).unwrap_or_default())));
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
+            let text = text.unwrap();
+            let parse_attempt = match default { Some(time) if text.is_empty() => Ok(time), _ => text.parse() };
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(NesClockFilter::new(parse_attempt.unwrap()))
         },
         KEY_NES_OVERREAD => {
             let text = cli_read(Some("Overread (true or false; true = HIGH, false = LOW): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(NesOverread::new(parse_attempt.unwrap()))
         },
         KEY_NES_GAME_GENIE_CODE => {
             let text = cli_read(Some("Game genie code: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(NesGameGenieCode::new(text.unwrap()))
         },
         
         KEY_SNES_CLOCK_FILTER => {
             let text = cli_read(Some("Clock filter (integer from 0-255; which will be multiplied by 0.1us): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(SnesClockFilter::new(parse_attempt.unwrap()))
         },
         KEY_SNES_OVERREAD => {
             let text = cli_read(Some("Overread (true or false; true = HIGH, false = LOW): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(SnesOverread::new(parse_attempt.unwrap()))
         },
         KEY_SNES_GAME_GENIE_CODE => {
             let text = cli_read(Some("Game genie code: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(SnesGameGenieCode::new(text.unwrap()))
         },
         KEY_SNES_LATCH_TRAIN => {
             let text = cli_read(Some("Latch trains (space-separated whole numbers): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let trains: Vec<u64> = text.unwrap().split_whitespace().map(|chunk| chunk.parse::<u64>().unwrap_or_default()).collect();
             Box::new(SnesLatchTrain::new(trains))
         },
         
         KEY_GENESIS_GAME_GENIE_CODE => {
             let text = cli_read(Some("Game genie code: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(GenesisGameGenieCode::new(text.unwrap()))
         },
         
@@ -480,470 +924,631 @@ fn create_packet(pretext: Option<&str>, exclude: Option<Vec<[u8; 2]>>) -> (bool,
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=255 {
-                let s = transition_index_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = transition_index_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Index type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let index_kind = kinds[selection - 1];
             
             let index = cli_read(Some("Index value: "));
-            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, None); }
+            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, vec![]); }
             let parse_attempt = index.unwrap().parse::<u64>();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             
             let mut options = Vec::new();
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=255 {
-                let s = transition_kind_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = transition_kind_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Transition type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let transition_kind = kinds[selection - 1];
             
             let mut payload = None;
             if transition_kind == 0xFF {
-                let create = create_packet(Some("Select a packet for this transition.\n"), Some(vec![KEY_DUMP_LAST_MODIFIED, KEY_INPUT_CHUNK, KEY_INPUT_MOMENT, KEY_TRANSITION, KEY_LAG_FRAME_CHUNK, KEY_MOVIE_TRANSITION]));
-                if create.1.is_none() { return (false, None); }
-                payload = create.1;
+                let mut create = create_packet(Some("Select a packet for this transition.\n"), Some(vec![KEY_DUMP_LAST_MODIFIED, KEY_INPUT_CHUNK, KEY_INPUT_MOMENT, KEY_TRANSITION, KEY_LAG_FRAME_CHUNK, KEY_MOVIE_TRANSITION]));
+                if create.1.len() > 1 { println!("Only a single packet can be used in a transition.\n"); }
+                if create.1.len() != 1 { return (false, vec![]); }
+                payload = create.1.pop();
             }
             
             Box::new(Transition::new(index_kind, parse_attempt.unwrap(), transition_kind, payload))
         },
         KEY_LAG_FRAME_CHUNK => {
             let index = cli_read(Some("Movie frame number: "));
-            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, None); }
+            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, vec![]); }
             let index = index.unwrap().parse::<u32>();
-            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, None); }
+            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, vec![]); }
             
             let length = cli_read(Some("Length of chunk: "));
-            if length.is_err() { println!("Err: {:?}\n", length.err().unwrap()); return (false, None); }
+            if length.is_err() { println!("Err: {:?}\n", length.err().unwrap()); return (false, vec![]); }
             let length = length.unwrap().parse::<u32>();
-            if length.is_err() { println!("Err: {:?}\n", length.err().unwrap()); return (false, None); }
+            if length.is_err() { println!("Err: {:?}\n", length.err().unwrap()); return (false, vec![]); }
             Box::new(LagFrameChunk::new(index.unwrap(), length.unwrap()))
         },
         KEY_MOVIE_TRANSITION => {
             let index = cli_read(Some("Frame number: "));
-            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, None); }
+            if index.is_err() { println!("Err: {:?}\n", index.err().unwrap()); return (false, vec![]); }
             let parse_attempt = index.unwrap().parse::<u32>();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             
             let mut options = Vec::new();
             let mut kinds = Vec::new();
             options.push("Return to add menu");
             for i in 1..=255 {
-                let s = transition_kind_lut(i);
-                if s.is_some() { options.push(s.unwrap()); kinds.push(i); }
+                if let Some(s) = transition_kind_lut(i) { options.push(s); kinds.push(i); }
             }
             let selection = cli_selection(&options, None, Some("Transition type[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             let transition_kind = kinds[selection - 1];
             
             let mut payload = None;
             if transition_kind == 0xFF {
-                let create = create_packet(Some("Select a packet for this transition.\n"), Some(vec![KEY_DUMP_LAST_MODIFIED, KEY_INPUT_CHUNK, KEY_INPUT_MOMENT, KEY_TRANSITION, KEY_LAG_FRAME_CHUNK, KEY_MOVIE_TRANSITION]));
-                if create.1.is_none() { return (true, None); }
-                payload = create.1;
+                let mut create = create_packet(Some("Select a packet for this transition.\n"), Some(vec![KEY_DUMP_LAST_MODIFIED, KEY_INPUT_CHUNK, KEY_INPUT_MOMENT, KEY_TRANSITION, KEY_LAG_FRAME_CHUNK, KEY_MOVIE_TRANSITION]));
+                if create.1.len() > 1 { println!("Only a single packet can be used in a transition.\n"); }
+                if create.1.len() != 1 { return (true, vec![]); }
+                payload = create.1.pop();
             }
             Box::new(MovieTransition::new(parse_attempt.unwrap(), transition_kind, payload))
         },
         
         KEY_COMMENT => {
             let text = cli_read(Some("Comment: "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             Box::new(Comment::new(text.unwrap()))
         },
         KEY_EXPERIMENTAL => {
             let text = cli_read(Some("Is this file experimental? (true or false): "));
-            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+            if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
             let parse_attempt = text.unwrap().parse::<bool>();
-            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, None); }
+            if parse_attempt.is_err() { println!("Err: {:?}\n", parse_attempt.err().unwrap()); return (false, vec![]); }
             Box::new(Experimental::new(parse_attempt.unwrap()))
         },
         KEY_UNSPECIFIED => {
             let selection = cli_selection(&["Return to add menu", "Text string", "Embed a file"], None, Some("Specify type of data[0]: "));
-            if selection == 0 { return (false, None); }
+            if selection == 0 { return (false, vec![]); }
             
             let payload = match selection {
                 1 => {
                     let text = cli_read(Some("Text: "));
-                    if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, None); }
+                    if text.is_err() { println!("Err: {:?}\n", text.err().unwrap()); return (false, vec![]); }
                     text.unwrap().as_bytes().to_vec()
                 },
                 2 => {
                     let path = cli_read(Some("Path to file containing arbitrary data: "));
-                    if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, None); }
+                    if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return (false, vec![]); }
                     let path = PathBuf::from(path.unwrap());
-                    if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, None); }
+                    if !path.exists() || !path.is_file() { println!("Path either doesn't exist or isn't a file.\n"); return (false, vec![]); }
                     let data_result = std::fs::read(path.clone());
-                    if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, None); }
+                    if data_result.is_err() { println!("Err: {:?}\n", data_result.err().unwrap()); return (false, vec![]); }
                     data_result.unwrap()
                 },
-                _ => return (false, None)
+                _ => return (false, vec![])
             };
             Box::new(Unspecified::new(payload))
         },
-        _ => { println!("Sorry, creating new packets of this type is currently unsupported.\n"); return (false, None); },
+        _ => { println!("Sorry, creating new packets of this type is currently unsupported.\n"); return (false, vec![]); },
     };
     
-    (false, Some(packet))
+    (false, vec![packet])
 }
 
 fn remove_menu(tasd: &mut TasdMovie) -> bool {
     let mut options = vec![String::from("Return to main menu")];
     for packet in &tasd.packets {
-        options.push(format!("{}", packet));
+        options.push(style().packet(packet.as_ref()));
     }
     
     let selection = cli_selection(&options.iter().map(|s| s as &str).collect::<Vec<&str>>(), Some("Select the packet you wish to remove.\n"), Some("Packet index[0]: "));
     if selection != 0 {
-        println!("Packet removed.\n");
         tasd.packets.remove(selection - 1);
-        tasd.save().unwrap();
+        match save_edits(tasd) {
+            Ok(()) => println!("Packet removed.\n"),
+            Err(err) => println!("Err: {}\n", err),
+        }
         return false;
     }
     
     true
 }
 
-fn import_tasvideos(_tasd: &mut TasdMovie) {
-    unimplemented!()
+fn import_tasvideos(_tasd: &mut TasdMovie) -> Result<()> {
+    Err(Error::Other("Importing from TASVideos isn't supported yet.".to_owned()))
 }
 
-fn display_packets(tasd: &TasdMovie, exclude_inputs: bool) {
-    let pretty = prettify_packets(tasd);
-    for packet in pretty {
-        if exclude_inputs && (packet.contains("INPUT_CHUNK") || packet.contains("INPUT_MOMENT")) && !packet.contains("TRANSITION") { continue; }
-        println!("{}", packet);
+fn display_packets(tasd: &TasdMovie, filter: &PacketFilter) {
+    let listing = PacketListing::new(tasd, style());
+    println!("{}", listing.header());
+    let matches = filter.apply(tasd);
+    if !pager::show(&listing, &matches) { return }
+    if matches.len() != tasd.packets.len() {
+        println!("{} of {} packets shown.", matches.len(), tasd.packets.len());
     }
-    println!("");
+    println!();
 }
 
-/*fn save_pretty(tasd: &TasdMovie) {
-    let pretty = prettify_packets(tasd);
-    let mut path = tasd.source_path.clone();
-    path.set_extension("tasd.pretty.txt");
-    let mut out = Vec::new();
-    for line in pretty {
-        format!("{}\n", line).as_bytes().iter().for_each(|byte| out.push(*byte));
-    }
+fn filter_menu() -> Result<PacketFilter> {
+    let mut filter = PacketFilter::default();
+    
+    let keys = cli_read(Some("Packet types, e.g. GAME_TITLE, COMMENT (blank for all): "))?;
+    filter.keys = filter::parse_keys(&keys)?;
+    let port = cli_read(Some("Port number (blank for all): "))?;
+    if !port.is_empty() { filter.port = Some(port.parse()?) }
+    let frames = cli_read(Some("Frame range, e.g. 100-200 or 100- (blank for all): "))?;
+    if !frames.is_empty() { filter.frames = Some(filter::parse_range(&frames)?) }
+    let pattern = cli_read(Some("Regular expression to search text for (blank for none): "))?;
+    if !pattern.is_empty() { filter.pattern = Some(regex::Regex::new(&pattern).map_err(|err| Error::Parse(err.to_string()))?) }
+    
+    Ok(filter)
+}
+
+fn save_pretty(tasd: &TasdMovie) {
+    let mut options = vec!["Return to main menu"];
+    ReportFormat::ALL.iter().for_each(|format| options.push(format.name()));
+    let selection = cli_selection(&options, Some("Select the format of the report.\n"), Some("Format[0]: "));
+    if selection == 0 { return; }
+    let format = ReportFormat::ALL[selection - 1];
     
-    let result = std::fs::write(path.clone(), out);
+    let default = tasd.source_path.with_extension(format.extension());
+    let path = cli_read(Some(&format!("Path to save to [{}]: ", default.to_string_lossy())));
+    if path.is_err() { println!("Err: {:?}\n", path.err().unwrap()); return; }
+    let path = path.unwrap();
+    let path = if path.is_empty() { default } else { PathBuf::from(path) };
+    
+    let result = std::fs::write(&path, report::render(tasd, format));
     if result.is_err() {
-        println!("Err: {:?}\n", result.err());
+        println!("Err: {:?}\n", result.err().unwrap());
     } else {
-        println!("File saved to: {}\n", path.canonicalize().unwrap().to_string_lossy())
+        println!("File saved to: {}\n", path.canonicalize().unwrap_or(path.clone()).to_string_lossy())
     }
-}*/
+}
 
-fn prettify_packets(tasd: &TasdMovie) -> Vec<String> {
-    let mut out = Vec::new();
+fn restore_menu(tasd: &mut TasdMovie) -> Result<()> {
+    let backups = file::backups(&tasd.source_path);
+    if backups.is_empty() { println!("No backups of this file exist yet. One is created the first time the file is saved in each session.\n"); return Ok(()); }
     
-    out.push(format!("Version: {:#06X}, Key Width: {}", tasd.version, tasd.keylen));
-    let padding = (tasd.packets.len() - 1).to_string().len();
-    for (i, packet) in tasd.packets.iter().enumerate() {
-        out.push(format!("[{}]: {}", format!("{:padding$}", i, padding=padding).cyan(), packet));
+    let mut options = vec![String::from("Return to main menu")];
+    for backup in &backups {
+        let modified = backup.modified.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "unknown time".to_owned());
+        options.push(format!("{} (saved {}, {} bytes)", backup.path.file_name().unwrap_or_default().to_string_lossy(), modified, backup.size));
     }
+    let selection = cli_selection(&options.iter().map(|s| s as &str).collect::<Vec<&str>>(), Some("Select the backup to restore. The current file will be backed up first.\n"), Some("Backup[0]: "));
+    if selection == 0 { return Ok(()); }
     
-    out
+    file::restore(tasd, &backups[selection - 1].path, &settings())?;
+    println!("Backup restored.\n");
+    Ok(())
 }
 
-fn load_tasd() -> Result<TasdMovie, DumpError> {
-    let result = cli_read(Some("Provide the name for a new empty file, or the path to an existing file you wish to load.\nFile name: "));
-    if result.is_ok() {
-        let mut name = result.unwrap();
-        if name.is_empty() {
-            return Err(DumpError::Custom("Err: Empty input. You must create or load a file to use this software.".to_owned()));
-        } else {
-            if !name.ends_with(".tasd") { name.push_str(".tasd") }
-            let mut path = PathBuf::from(name);
-            check_tasd_exists_create(&mut path);
-            TasdMovie::new(&path)
-        }
+fn autofill_menu(tasd: &mut TasdMovie) -> Result<()> {
+    let path = PathBuf::from(cli_read(Some("Path to ROM file: "))?);
+    if !path.is_file() { return Err("Path either doesn't exist or isn't a file.".into()) }
+    let data = std::fs::read(&path)?;
+    let info = rom::read_header(&path, &data).ok_or("Unable to recognize the ROM's header. Supported: iNES/NES 2.0, SNES, GB/GBC, GBA, and Genesis.")?;
+    
+    println!("Detected {} ROM.", console_type_lut(info.console_type).unwrap_or("Unknown"));
+    info.details.iter().for_each(|detail| println!("  {}", detail));
+    println!();
+    
+    let mut packets = rom::proposed_packets(&info, &path);
+    packets.retain(|packet| {
+        let exists = !tasd.search_by_key(vec![packet.key()]).is_empty();
+        if exists { println!("Skipping {}, since the file already has one.", style().packet(packet.as_ref())) }
+        !exists
+    });
+    if packets.is_empty() { println!("Nothing to add.\n"); return Ok(()); }
+    
+    println!("Proposed packets:");
+    packets.iter().for_each(|packet| println!("  {}", style().packet(packet.as_ref())));
+    println!();
+    let selection = cli_selection(&["Return to main menu", "Add these packets"], None, Some("Option[0]: "));
+    if selection == 0 { return Ok(()); }
+    
+    tasd.packets.extend(packets);
+    save_edits(tasd)?;
+    println!("Metadata has been added to the file.\n");
+    Ok(())
+}
+
+fn template_menu(tasd: &mut TasdMovie) -> Result<()> {
+    let path = cli_read(Some("Path to template (.toml, or a .tasd file to copy metadata from): "))?;
+    if path.is_empty() { return Ok(()) }
+    let template = Template::load(Path::new(&path))?;
+    
+    let added = template.apply(tasd);
+    save_edits(tasd)?;
+    println!("Added {} packet(s) from the template. It will also be applied to legacy files imported from now on.\n", added);
+    *TEMPLATE.lock().unwrap() = Some(template);
+    
+    Ok(())
+}
+
+/// Saves the file after an edit, unless auto-save is off, in which case the edit is kept until 'Save changes'.
+fn save_edits(tasd: &mut TasdMovie) -> Result<()> {
+    let settings = settings();
+    if settings.config.auto_save {
+        file::save(tasd, &settings)
     } else {
-        return Err(DumpError::Custom(format!("Err: {:?}", result.err().unwrap())));
+        UNSAVED.store(true, Ordering::Relaxed);
+        Ok(())
     }
 }
 
-fn import_legacy(tasd_option: &mut Option<TasdMovie>, path: Option<&PathBuf>) -> Result<(), String> {
-    let path = if let Some(path) = path {
-        path.to_owned()
-    } else {
-        let result = cli_read(Some("Path to .r08, .r16m, or GBI(.txt) file: "));
-        if result.is_err() { return Err(format!("Err: {:?}", result.err())) }
-        PathBuf::from(result.unwrap())
-    };
-    if !path.exists() || path.is_dir() { return Err("Err: File either doesn't exist or is a directory.".to_owned()) }
-    if path.extension().is_none() { return Err("Err: Unable to identify file, make sure extension is correct.".to_owned())}
-    let extension = path.extension().unwrap().to_string_lossy().to_string();
-    
-    if tasd_option.is_none() {
-        let mut tasd = TasdMovie::default();
-        tasd.source_path = path.clone();
-        tasd.source_path.set_extension("tasd");
-        *tasd_option = Some(tasd);
-    }
-    let tasd = tasd_option.as_mut().unwrap();
-    
-    match extension.as_str() {
-        "r08" => {
-            let result = std::fs::read(path);
-            if result.is_err() { return Err(format!("Err: {:?}", result.err())) }
-            let mut result = result.unwrap();
-            if result.len() % 2 == 1 { result.push(0xFF) } // Shouldn't ever be misaligned, but is safer to double check
-            tasd.packets.push(Box::new(ConsoleType::new(0x01, None)));
-            tasd.packets.push(Box::new(PortController::new(1, 0x0101)));
-            tasd.packets.push(Box::new(PortController::new(2, 0x0101)));
-            
-            let mut port1 = Vec::new();
-            let mut port2 = Vec::new();
-            for i in 0..(result.len() / 2) {
-                port1.push(result[(i * 2)] ^ 0xFF);
-                port2.push(result[(i * 2) + 1] ^ 0xFF);
-            }
-            tasd.packets.push(Box::new(InputChunk::new(1, port1)));
-            tasd.packets.push(Box::new(InputChunk::new(2, port2)));
-            
-            tasd.save().unwrap();
-            println!("Legacy file data has been imported and saved.\n");
-            Ok(())
+/// Asks whether to save edits before the file is closed. Returns false if the file should stay open.
+fn offer_save(tasd: &mut TasdMovie) -> bool {
+    if !UNSAVED.load(Ordering::Relaxed) { return true }
+    
+    let selection = cli_selection(&["Save them", "Discard them", "Return to main menu"], Some("There are unsaved changes.\n"), Some("Option[0]: "));
+    match selection {
+        0 => match file::save(tasd, &settings()) {
+            Ok(()) => println!("File saved.\n"),
+            Err(err) => { println!("Err: {}\n", err); return false },
         },
-        "r16m" => {
-            let result = std::fs::read(path);
-            if result.is_err() { return Err(format!("Err: {:?}", result.err())) }
-            let mut result = result.unwrap();
-            if result.len() % 2 == 1 { result.push(0) } // Shouldn't ever be misaligned, but is safer to double check
-            if result.len() % 4 == 1 { result.push(0); result.push(0); } // Shouldn't ever be misaligned, but is safer to double check
-            tasd.packets.push(Box::new(ConsoleType::new(0x02, None)));
-            tasd.packets.push(Box::new(PortController::new(1, 0x0201)));
-            tasd.packets.push(Box::new(PortController::new(2, 0x0201)));
-            
-            let mut port1 = Vec::new();
-            let mut port2 = Vec::new();
-            for i in 0..(result.len() / 4) {
-                port1.push(result[(i * 4)] ^ 0xFF);
-                port1.push(result[(i * 4) + 1] ^ 0xFF);
-                port2.push(result[(i * 4) + 2] ^ 0xFF);
-                port2.push(result[(i * 4) + 3] ^ 0xFF);
-            }
-            tasd.packets.push(Box::new(InputChunk::new(1, port1)));
-            tasd.packets.push(Box::new(InputChunk::new(2, port2)));
-            
-            tasd.save().unwrap();
-            println!("Legacy file data has been imported and saved.\n");
-            Ok(())
+        2 => return false,
+        _ => (),
+    }
+    UNSAVED.store(false, Ordering::Relaxed);
+    true
+}
+
+fn settings_menu(tasd: &mut TasdMovie) -> Result<()> {
+    let path = Config::path().ok_or("Unable to find a config directory. Set XDG_CONFIG_HOME or HOME.")?;
+    let mut config = Config::load()?;
+    loop {
+        let unset = || "not set".to_owned();
+        let options = [
+            "Return to main menu".to_owned(),
+            format!("Export directory: {}", config.export_dir.as_ref().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_else(|| "next to the TASD file".to_owned())),
+            format!("Color: {}", config.color.unwrap_or(ColorMode::Auto).name()),
+            format!("Auto-save: {}", if config.auto_save { "on" } else { "off" }),
+            format!("Default NES latch filter: {}", config.nes_latch_filter.map(|time| time.to_string()).unwrap_or_else(unset)),
+            format!("Default NES clock filter: {}", config.nes_clock_filter.map(|time| time.to_string()).unwrap_or_else(unset)),
+            format!("Default attribution name: {}", config.attribution.clone().unwrap_or_else(unset)),
+        ];
+        let selection = cli_selection(&options.iter().map(|s| s as &str).collect::<Vec<&str>>(), Some(&format!("Settings are stored in {}\n", path.to_string_lossy())), Some("Setting[0]: "));
+        match selection {
+            1 => {
+                let dir = cli_read(Some("Export directory (blank to export next to the TASD file): "))?;
+                config.export_dir = if dir.is_empty() { None } else { Some(PathBuf::from(dir)) };
+            },
+            2 => config.color = Some([ColorMode::Auto, ColorMode::Always, ColorMode::Never][cli_selection(&["auto", "always", "never"], None, Some("Color[0]: "))]),
+            3 => config.auto_save = !config.auto_save,
+            4 => {
+                let time = cli_read(Some("Latch filter (integer from 0-65535; which will be multiplied by 1.0us), or blank for none: "))?;
+                config.nes_latch_filter = if time.is_empty() { None } else { Some(time.parse()?) };
+            },
+            5 => {
+                let time = cli_read(Some("Clock filter (integer from 0-255; which will be multiplied by 0.1us), or blank for none: "))?;
+                config.nes_clock_filter = if time.is_empty() { None } else { Some(time.parse()?) };
+            },
+            6 => {
+                let name = cli_read(Some("Attribution name (blank for none): "))?;
+                config.attribution = if name.is_empty() { None } else { Some(name) };
+            },
+            _ => return Ok(()),
+        }
+        
+        config.save()?;
+        *STYLE.lock().unwrap() = Style::new(config.color.unwrap_or(ColorMode::Auto));
+        settings().config = config.clone();
+        if config.auto_save && UNSAVED.load(Ordering::Relaxed) {
+            file::save(tasd, &settings())?;
+            UNSAVED.store(false, Ordering::Relaxed);
+            println!("Auto-save is on, so your unsaved changes have been saved.");
+        }
+        println!("Settings saved.\n");
+    }
+}
+
+fn load_tasd() -> Result<TasdMovie> {
+    let mut name = cli_read(Some("Provide the name for a new empty file, or the path to an existing file you wish to load.\nFile name: "))?;
+    if name.is_empty() { return Err("Empty input. You must create or load a file to use this software.".into()) }
+    
+    if !name.ends_with(".tasd") { name.push_str(".tasd") }
+    let mut path = PathBuf::from(name);
+    check_tasd_exists_create(&mut path)?;
+    let tasd = file::load(&path)?;
+    warn_version(&tasd);
+    
+    Ok(tasd)
+}
+
+fn warn_version(tasd: &TasdMovie) {
+    if let Some(warning) = file::version_warning(tasd) { println!("{}\n", warning) }
+}
+
+/// Prints every problem the validator finds. Returns whether the file has none.
+fn print_issues(tasd: &TasdMovie) -> bool {
+    let issues = validate::validate(tasd);
+    if issues.is_empty() { println!("No problems found."); return true }
+    
+    println!("Found {} problem(s):", issues.len());
+    issues.iter().for_each(|issue| println!("  {}", issue));
+    false
+}
+
+fn unknown_menu(tasd: &mut TasdMovie) -> Result<()> {
+    let indexes = unknown::unknown_packets(tasd);
+    if indexes.is_empty() { println!("This file doesn't have any unknown or unreadable packets.\n"); return Ok(()) }
+    
+    let mut options = vec![String::from("Return to main menu")];
+    for i in &indexes {
+        options.push(format!("[{}]: {}", i, style().packet(tasd.packets[*i].as_ref())));
+    }
+    let selection = cli_selection(&options.iter().map(|s| s as &str).collect::<Vec<&str>>(), Some("Select the packet to export or remove.\n"), Some("Packet[0]: "));
+    if selection == 0 { return Ok(()) }
+    let index = indexes[selection - 1];
+    let packet = tasd.packets[index].as_any().downcast_ref::<UnknownPacket>().unwrap().clone();
+    
+    match cli_selection(&["Return to main menu", "Export its payload to a file", "Remove it"], None, Some("Option[0]: ")) {
+        1 => {
+            let default = tasd.source_path.with_extension(format!("{}.{}.bin", index, fields::encode_hex(packet.key_bytes())));
+            let path = cli_read(Some(&format!("Path to save to [{}]: ", default.to_string_lossy())))?;
+            let path = if path.is_empty() { default } else { PathBuf::from(path) };
+            std::fs::write(&path, packet.payload())?;
+            println!("{} bytes saved to: {}\n", packet.payload().len(), path.to_string_lossy());
         },
-        "txt" => {
-            let selection = cli_selection(&["GB", "GBC", "GBA"], Some("Which handheld is this for?\n"), Some("Handheld type[0]: "));
-            let result = std::fs::read_to_string(path);
-            if result.is_err() { return Err(format!("Err: {:?}", result.err())) }
-            let result = result.unwrap();
-            let result = result.lines();
-            match selection {
-                0 => {
-                    tasd.packets.push(Box::new(ConsoleType::new(0x05, None)));
-                    tasd.packets.push(Box::new(PortController::new(1, 0x0501)));
-                },
-                1 => {
-                    tasd.packets.push(Box::new(ConsoleType::new(0x06, None)));
-                    tasd.packets.push(Box::new(PortController::new(1, 0x0601)));
-                },
-                2 => {
-                    tasd.packets.push(Box::new(ConsoleType::new(0x07, None)));
-                    tasd.packets.push(Box::new(PortController::new(1, 0x0701)));
-                },
-                _ => ()
-            }
-            
-            for line in result {
-                let parts = line.split_once(' ');
-                if parts.is_some() {
-                    let parts = parts.unwrap();
-                    let clock = u64::from_str_radix(parts.0, 16).unwrap();
-                    let input = tasd::util::to_bytes(u16::from_str_radix(parts.1, 16).unwrap() as usize, 2);
-                    match selection {
-                        0 => { tasd.packets.push(Box::new(InputMoment::new(1, 0x02, clock, vec![input[1] ^ 0xFF]))) },
-                        1 => { tasd.packets.push(Box::new(InputMoment::new(1, 0x02, clock, vec![input[1] ^ 0xFF]))) },
-                        2 => { tasd.packets.push(Box::new(InputMoment::new(1, 0x02, clock, vec![input[0] ^ 0xFF, input[1] ^ 0xFF]))) },
-                        _ => ()
-                    }
-                }
-            }
-            
-            tasd.save().unwrap();
-            println!("Legacy file data has been imported and saved.\n");
-            Ok(())
+        2 => {
+            tasd.packets.remove(index);
+            save_edits(tasd)?;
+            println!("Packet removed.\n");
         },
-        _ => Err("Unable to identify file, make sure extension is correct.".to_owned())
+        _ => (),
     }
+    Ok(())
+}
+
+fn inspect_menu(tasd: &TasdMovie) -> Result<()> {
+    if tasd.packets.is_empty() { println!("This file doesn't have any packets.\n"); return Ok(()) }
+    let index = cli_read(Some(&format!("Packet index (0-{}): ", tasd.packets.len() - 1)))?.parse::<usize>()?;
+    
+    // Offsets are only meaningful for the bytes actually on disk, so edits which haven't been saved are shown as they're currently serialized
+    let data = if UNSAVED.load(Ordering::Relaxed) {
+        println!("Note: This file has unsaved changes. Offsets include them, but may still shift when saving updates DUMP_LAST_MODIFIED.");
+        raw::dump(tasd)?
+    } else {
+        std::fs::read(&tasd.source_path)?
+    };
+    let inspection = inspect::Inspection::new(&data, index)?;
+    inspection.describe().iter().for_each(|line| println!("{}", line));
+    println!();
+    
+    let (name, payload) = match inspection.extractable() {
+        Some(extractable) => extractable,
+        None => return Ok(()),
+    };
+    if cli_selection(&["Return to main menu", "Extract payload to a file"], None, Some("Option[0]: ")) != 1 { return Ok(()) }
+    let default = tasd.source_path.with_file_name(name);
+    let path = cli_read(Some(&format!("Path to save to [{}]: ", default.to_string_lossy())))?;
+    let path = if path.is_empty() { default } else { PathBuf::from(path) };
+    std::fs::write(&path, payload)?;
+    println!("{} bytes saved to: {}\n", payload.len(), path.to_string_lossy());
+    Ok(())
 }
 
-fn export_legacy(tasd: &TasdMovie) {
-    let valid_types = [0x01, 0x02, 0x05, 0x06, 0x07];
-    let search = tasd.search_by_key(vec![KEY_CONSOLE_TYPE]);
-    let mut packets = Vec::new();
-    for packet in search {
-        let packet = packet.as_any().downcast_ref::<ConsoleType>().unwrap();
-        if valid_types.contains(&packet.kind) {
-            packets.push(packet);
+fn keylen_menu(tasd: &mut TasdMovie) -> Result<()> {
+    println!("Keys are currently {} byte(s) wide. Every key keeps its value, so a file can be widened freely, but only narrowed if all of its keys still fit.", tasd.keylen);
+    let keylen = cli_read(Some("New key width in bytes (blank to keep it): "))?;
+    if keylen.is_empty() { return Ok(()) }
+    
+    file::set_keylen(tasd, parse_keylen(&keylen)?)?;
+    save_edits(tasd)?;
+    println!("Key width changed to {}.\n", tasd.keylen);
+    Ok(())
+}
+
+/// Opens an existing TASD file without creating one if it is missing.
+fn open_existing_tasd(path: &str) -> Result<TasdMovie> {
+    let path = PathBuf::from(path);
+    if !path.is_file() { return Err(format!("{} either doesn't exist or isn't a file.", path.to_string_lossy()).into()) }
+    
+    let tasd = file::load(&path)?;
+    // Written to stderr so that output such as `diff --json` stays machine-readable
+    if let Some(warning) = file::version_warning(&tasd) { eprintln!("{}", warning) }
+    Ok(tasd)
+}
+
+fn import_legacy(tasd_option: &mut Option<TasdMovie>, path: Option<&PathBuf>) -> Result<()> {
+    let path = if let Some(path) = path {
+        path.to_owned()
+    } else {
+        PathBuf::from(cli_read(Some(&format!("Path to legacy file ({}): ", convert::import_extensions())))?)
+    };
+    if !path.exists() || path.is_dir() { return Err("File either doesn't exist or is a directory.".into()) }
+    
+    let data = std::fs::read(&path)?;
+    let importer = convert::detect_importer(&path, &data).ok_or_else(|| format!("Unable to identify file, make sure extension is correct ({}).", convert::import_extensions()))?;
+    let console_types = importer.console_types();
+    let console_type = if console_types.len() == 1 {
+        console_types[0]
+    } else {
+        let names: Vec<&str> = console_types.iter().map(|kind| console_type_lut(*kind).unwrap_or("Unknown")).collect();
+        console_types[cli_selection(&names, Some("Which console is this for?\n"), Some("Console type[0]: "))]
+    };
+    
+    let appending = tasd_option.is_some();
+    let mut tasd = tasd_option.take().unwrap_or_else(|| provenance::new_movie(path.with_extension("tasd"), settings().new_file_header));
+    let result = if appending {
+        convert::append_into(&mut tasd, importer, &path, &data, console_type, &settings(), resolve_conflict)
+    } else {
+        convert::import_into(&mut tasd, importer, &path, &data, console_type, &settings())
+    };
+    if result.is_ok() {
+        if let Some(template) = TEMPLATE.lock().unwrap().as_ref() {
+            println!("Added {} packet(s) from the metadata template.", template.apply(&mut tasd));
         }
     }
+    *tasd_option = Some(tasd);
+    result?;
     
-    let console_type = match packets.len() {
-        0 => { println!("Unable to determine what console this data is intended for. Please add a ConsoleType packet."); return; },
-        1 => packets[0].kind,
+    if appending && !settings().config.auto_save {
+        save_edits(tasd_option.as_mut().unwrap())?;
+        println!("Legacy file data has been imported. Use 'Save changes' to write it to the file.\n");
+    } else {
+        file::save(tasd_option.as_mut().unwrap(), &settings())?;
+        println!("Legacy file data has been imported and saved.\n");
+    }
+    Ok(())
+}
+
+/// Asks which packet to keep when an appended legacy file's console or controller differs from the open file's.
+fn resolve_conflict(existing: &dyn Packet, imported: &dyn Packet) -> ConflictResolution {
+    let pretext = format!("The file has {}, but the imported file has {}.\n", style().packet(existing), style().packet(imported));
+    match cli_selection(&["Keep the existing packet", "Use the imported packet", "Cancel the import"], Some(&pretext), Some("Option[0]: ")) {
+        1 => ConflictResolution::UseImported,
+        2 => ConflictResolution::Cancel,
+        _ => ConflictResolution::KeepExisting,
+    }
+}
+
+fn export_legacy(tasd: &TasdMovie) -> Result<()> {
+    let console_types = convert::export_console_types(tasd);
+    let console_type = match console_types.len() {
+        0 => return Err("Unable to determine what console this data is intended for. Please add a ConsoleType packet.".into()),
+        1 => console_types[0],
         _ => {
             let mut options = vec!["Return to main menu"];
-            for packet in &packets {
-                if let Some(kind) = console_type_lut(packet.kind) {
-                    options.push(kind);
-                }
+            for kind in &console_types {
+                options.push(console_type_lut(*kind).unwrap_or("Unknown"));
             }
             let selection = cli_selection(&options, Some("Multiple console types detected. Select which you're trying to export to."), Some("Console type[0]: "));
-            if selection == 0 { return; }
+            if selection == 0 { return Ok(()); }
             
-            packets[selection - 1].kind 
+            console_types[selection - 1]
         },
     };
+    let exporter = convert::exporter_for_console(console_type).unwrap();
     
-    match console_type {
-        0x01 => { // NES (.r08)
-            let path = tasd.source_path.with_extension("export.r08");
-            let search = tasd.search_by_key(vec![KEY_INPUT_CHUNK]);
-            
-            let mut port1 = Vec::new();
-            let mut port2 = Vec::new();
-            for packet in search {
-                let chunk = packet.as_any().downcast_ref::<InputChunk>().unwrap();
-                if chunk.port == 1 { chunk.inputs.iter().for_each(|byte| port1.push(*byte ^ 0xFF)) }
-                if chunk.port == 2 { chunk.inputs.iter().for_each(|byte| port2.push(*byte ^ 0xFF)) }
-            }
-            
-            // if they are different lengths, make up the difference with default inputs
-            let max_len = max(port1.len(), port2.len());
-            for _ in 0..(max_len - port1.len()) { port1.push(0xFF) }
-            for _ in 0..(max_len - port2.len()) { port2.push(0xFF) }
-            
-            let mut out = Vec::new();
-            for i in 0..max_len {
-                out.push(port1[i]);
-                out.push(port2[i]);
-            }
-            
-            let result = std::fs::write(path.clone(), out);
-            if result.is_err() { println!("Err: {:?}\n", result.err().unwrap()); return; }
-            println!("Legacy file data has been exported to: {}\n", path.canonicalize().unwrap().to_string_lossy());
-        },
-        0x02 => { // SNES (.r16m)
-            let path = tasd.source_path.with_extension("export.r16m");
-            let search = tasd.search_by_key(vec![KEY_INPUT_CHUNK]);
-            
-            let mut port1 = Vec::new();
-            let mut port2 = Vec::new();
-            let mut port3 = Vec::new();
-            let mut port4 = Vec::new();
-            for packet in search {
-                let chunk = packet.as_any().downcast_ref::<InputChunk>().unwrap();
-                if chunk.port == 1 { chunk.inputs.iter().for_each(|byte| port1.push(*byte ^ 0xFF)) }
-                if chunk.port == 2 { chunk.inputs.iter().for_each(|byte| port2.push(*byte ^ 0xFF)) }
-                if chunk.port == 3 { chunk.inputs.iter().for_each(|byte| port3.push(*byte ^ 0xFF)) }
-                if chunk.port == 4 { chunk.inputs.iter().for_each(|byte| port4.push(*byte ^ 0xFF)) }
-            }
-            
-            // if they are different lengths, make up the difference with default inputs
-            let max_len = max(port1.len(), port2.len());
-            for _ in 0..(max_len - port1.len()) { port1.push(0xFF) }
-            for _ in 0..(max_len - port2.len()) { port2.push(0xFF) }
-            
-            let mut out = Vec::new();
-            for i in 0..(max_len / 2) {
-                out.push(port1[(i * 2)]);
-                out.push(port1[(i * 2) + 1]);
-                out.push(port2[(i * 2)]);
-                out.push(port2[(i * 2) + 1]);
-            }
-            
-            let result = std::fs::write(path.clone(), out);
-            if result.is_err() { println!("Err: {:?}\n", result.err().unwrap()); return; }
-            println!("Legacy file data has been exported to: {}\n", path.canonicalize().unwrap().to_string_lossy());
-        },
-        0x05 | 0x06 => { // GB/C (GBI .txt)
-            let path = tasd.source_path.with_extension("export.txt");
-            let search = tasd.search_by_key(vec![KEY_INPUT_MOMENT]);
-            let mut out = Vec::new();
-            for packet in search {
-                let moment = packet.as_any().downcast_ref::<InputMoment>().unwrap();
-                let line = format!("{:08X} {:04X}\n", moment.index, (moment.inputs[0] ^ 0xFF) as u16);
-                
-                line.as_bytes().iter().for_each(|byte| out.push(*byte));
-            }
-            
-            let result = std::fs::write(path.clone(), out);
-            if result.is_err() { println!("Err: {:?}\n", result.err().unwrap()); return; }
-            println!("Legacy file data has been exported to: {}\n", path.canonicalize().unwrap().to_string_lossy());
-        },
-        0x07 => { // GBA (GBI .txt)
-            let path = tasd.source_path.with_extension("export.txt");
-            let search = tasd.search_by_key(vec![KEY_INPUT_MOMENT]);
-            let mut out = Vec::new();
-            for packet in search {
-                let moment = packet.as_any().downcast_ref::<InputMoment>().unwrap();
-                let line = format!("{:08X} {:04X}\r\n", moment.index, u16::from_be_bytes(moment.inputs.as_slice().try_into().unwrap()) ^ 0xFFFF);
-                
-                line.as_bytes().iter().for_each(|byte| out.push(*byte));
-            }
-            
-            let result = std::fs::write(path.clone(), out);
-            if result.is_err() { println!("Err: {:?}\n", result.err().unwrap()); return; }
-            println!("Legacy file data has been exported to: {}\n", path.canonicalize().unwrap().to_string_lossy());
-        },
-        _ => ()
+    let path = convert::export_path(&tasd.source_path, exporter, &settings());
+    convert::write_output(&path, &exporter.export(tasd, console_type)?)?;
+    println!("Legacy file data has been exported to: {}\n", path.canonicalize().unwrap_or(path.clone()).to_string_lossy());
+    Ok(())
+}
+
+/// Parses a TASD version number, e.g. `0x0001` or `1`.
+fn parse_version(text: &str) -> Result<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => Ok(u16::from_str_radix(hex, 16)?),
+        None => Ok(text.parse()?),
+    }
+}
+
+fn parse_keylen(text: &str) -> Result<u8> {
+    match text.parse()? {
+        keylen @ 1..=8 => Ok(keylen),
+        keylen => Err(Error::Parse(format!("Key width must be from 1 to 8 bytes, not {}", keylen))),
+    }
+}
+
+/// Parses a console type name such as `GBA`, or a console type number such as `0x07`.
+fn parse_console_type(text: &str) -> Result<u8> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return Ok(u8::from_str_radix(hex, 16)?);
+    }
+    (0x01..=0xFF).find(|kind| console_type_lut(*kind).is_some_and(|name| name.eq_ignore_ascii_case(text)))
+        .ok_or_else(|| Error::Parse(format!("Unknown console type: {}", text)))
+}
+
+fn inspect_command(matches: &ArgMatches) -> Result<()> {
+    let input = PathBuf::from(matches.value_of("input").unwrap());
+    let index = matches.value_of("index").unwrap().parse::<usize>()?;
+    let data = std::fs::read(&input)?;
+    let inspection = inspect::Inspection::new(&data, index)?;
+    inspection.describe().iter().for_each(|line| println!("{}", line));
+    
+    if matches.is_present("extract") {
+        let (name, payload) = inspection.extractable().ok_or("Only UNSPECIFIED, MOVIE_FILE, and MEMORY_INIT packets with data can be extracted.")?;
+        let path = matches.value_of("extract").map(PathBuf::from).unwrap_or_else(|| input.with_file_name(name));
+        std::fs::write(&path, payload)?;
+        println!("\n{} bytes saved to: {}", payload.len(), path.to_string_lossy());
     }
+    Ok(())
+}
+
+fn import_command(matches: &ArgMatches) -> Result<PathBuf> {
+    let input = PathBuf::from(matches.value_of("input").unwrap());
+    let output = matches.value_of("output").map(PathBuf::from).unwrap_or_else(|| input.with_extension("tasd"));
+    let data = std::fs::read(&input)?;
+    let importer = convert::resolve_importer(&input, &data, matches.value_of("format").map(|name| convert::importer_by_name(name).unwrap()))?;
+    let console_type = convert::import_console_type(importer, matches.value_of("console").map(parse_console_type).transpose()?)?;
+    
+    convert::import_file(&input, &data, &output, importer, console_type, TEMPLATE.lock().unwrap().as_ref(), &settings())?;
+    Ok(output)
+}
+
+fn export_command(matches: &ArgMatches) -> Result<PathBuf> {
+    let input = PathBuf::from(matches.value_of("input").unwrap());
+    if !input.is_file() { return Err(format!("{} either doesn't exist or isn't a file.", input.to_string_lossy()).into()) }
+    let exporter = matches.value_of("format").map(|name| convert::exporter_by_name(name).unwrap());
+    let console_type = matches.value_of("console").map(parse_console_type).transpose()?;
+    
+    convert::export_file(&input, matches.value_of("output").map(Path::new), exporter, console_type, &settings())
 }
 
-fn cli_read(pretext: Option<&str>) -> Result<String, Option<Error>> {
-    if pretext.is_some() {
-        print!("{}", pretext.unwrap());
+/// Runs a batch conversion and prints its report. Returns whether every file was converted.
+fn batch_command(matches: &ArgMatches) -> Result<bool> {
+    let console_type = matches.value_of("console").map(parse_console_type).transpose()?;
+    let template = TEMPLATE.lock().unwrap().clone();
+    let mode = if matches.is_present("export") {
+        let exporter = matches.value_of("format").map(|name| convert::exporter_by_name(name).ok_or_else(|| format!("{} can't be exported to.", name))).transpose()?;
+        BatchMode::Export { exporter, console_type }
+    } else {
+        let importer = matches.value_of("format").map(|name| convert::importer_by_name(name).ok_or_else(|| format!("{} can't be imported.", name))).transpose()?;
+        BatchMode::Import { importer, console_type, template: template.as_ref() }
+    };
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => jobs.parse()?,
+        None => std::thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1),
+    };
+    let out_dir = matches.value_of("out-dir").map(PathBuf::from);
+    if let Some(dir) = &out_dir { std::fs::create_dir_all(dir)?; }
+    
+    let inputs = batch::collect_inputs(&matches.values_of("inputs").unwrap().collect::<Vec<_>>(), &mode)?;
+    if inputs.is_empty() { return Err("No files to convert.".into()) }
+    let results = batch::run(&inputs, mode, out_dir.as_deref(), jobs, &settings());
+    
+    let report = batch::report(&results);
+    print!("{}", report);
+    if let Some(path) = matches.value_of("report") {
+        std::fs::write(path, &report)?;
+        println!("\nReport has been written to: {}", path);
+    }
+    
+    Ok(results.iter().all(|result| result.output.is_ok()))
+}
+
+fn cli_read(pretext: Option<&str>) -> Result<String> {
+    if let Some(pretext) = pretext {
+        print!("{}", pretext);
         flush();
     }
     
     let mut cli_input = String::new();
-    let result = std::io::stdin().read_line(&mut cli_input);
-    if result.is_err() {
-        return Err(result.err());
-    }
+    std::io::stdin().read_line(&mut cli_input)?;
     
-    println!("");
+    println!();
     
     Ok(cli_input.trim().to_string())
 }
 
-fn cli_selection<'a>(list: &[&str], pretext: Option<&str>, posttext: Option<&str>) -> usize {
-    if pretext.is_some() {
-        print!("{}", pretext.unwrap());
+fn cli_selection(list: &[&str], pretext: Option<&str>, posttext: Option<&str>) -> usize {
+    if let Some(pretext) = pretext {
+        print!("{}", pretext);
     }
-    let padding = (list.len() - 1).to_string().len();
+    let padding = list.len().saturating_sub(1).to_string().len();
     for (i, element) in list.iter().enumerate() {
-        println!("[{}]: {}", format!("{:padding$}", i, padding=padding).cyan(), element);
+        println!("[{}]: {}", style().paint(format!("{:padding$}", i, padding=padding), Color::Cyan), element);
     }
-    if posttext.is_some() {
-        print!("{}", posttext.unwrap());
+    if let Some(posttext) = posttext {
+        print!("{}", posttext);
         flush();
     }
     
-    let result = cli_read(None);
-    if result.is_ok() {
-        let text = result.unwrap();
-        
-        if !text.is_empty() {
-            let result = text.parse::<usize>();
-            if result.is_ok() {
-                let selection = result.unwrap();
-                if (0..list.len()).any(|i| i == selection) {
-                    return selection;
-                }
+    if let Ok(text) = cli_read(None) {
+        if let Ok(selection) = text.parse::<usize>() {
+            if (0..list.len()).any(|i| i == selection) {
+                return selection;
             }
         }
     }
@@ -951,31 +1556,33 @@ fn cli_selection<'a>(list: &[&str], pretext: Option<&str>, posttext: Option<&str
     0
 }
 
-fn check_tasd_exists_create(path_ref: &mut PathBuf) {
+fn check_tasd_exists_create(path_ref: &mut PathBuf) -> Result<()> {
     let mut path = path_ref.clone();
     if !path.extension().unwrap_or(OsStr::new("")).eq_ignore_ascii_case("tasd") { path = path.with_extension("tasd"); }
     if !path.exists() || !path.is_file() {
-        let parent = path.parent();
-        if parent.is_some() {
-            std::fs::create_dir_all(parent.unwrap()).unwrap();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
         
-        let result = std::fs::write(path.clone(), NEW_TASD_FILE);
-        if result.is_err() {
-            println!("Error: {:?}", result.err());
-            exit(true, 1);
-        }
+        let mut tasd = provenance::new_movie(path.clone(), settings().new_file_header);
+        let version = cli_read(Some(&format!("TASD version [{:#06X}]: ", tasd.version)))?;
+        if !version.is_empty() { tasd.version = parse_version(&version)? }
+        let keylen = cli_read(Some(&format!("Key width in bytes [{}]: ", tasd.keylen)))?;
+        if !keylen.is_empty() { file::set_keylen(&mut tasd, parse_keylen(&keylen)?)? }
+        file::save(&mut tasd, &settings())?;
         println!("Created new file: {}\n", path.to_string_lossy());
     } else {
         println!("Existing file found.\n");
     }
     flush();
     *path_ref = path;
+    
+    Ok(())
 }
 
-fn exit(pause: bool, code: i32) {
+fn exit(pause: bool, code: i32) -> ! {
     if pause {
-        cli_read(Some("\nPress enter to exit...")).unwrap();
+        let _ = cli_read(Some("\nPress enter to exit..."));
     }
     std::process::exit(code);
 }
@@ -983,3 +1590,28 @@ fn exit(pause: bool, code: i32) {
 fn flush() {
     stdout().flush().expect("Flushing stdout failed. How did that happen?!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn global_options_go_before_or_after_subcommands() {
+        for args in [["tasd-edit", "--color", "never", "info", "x.tasd"], ["tasd-edit", "info", "x.tasd", "--color", "never"]] {
+            let matches = cli().try_get_matches_from(args).unwrap();
+            assert_eq!(matches.subcommand_matches("info").unwrap().value_of("color"), Some("never"));
+        }
+        
+        let matches = cli().try_get_matches_from(["tasd-edit", "--no-last-modified", "--template", "t.toml", "batch", "."]).unwrap();
+        let batch = matches.subcommand_matches("batch").unwrap();
+        assert!(batch.is_present("no-last-modified"));
+        assert_eq!(batch.value_of("template"), Some("t.toml"));
+    }
+    
+    #[test]
+    fn opens_a_file_without_a_subcommand() {
+        let matches = cli().try_get_matches_from(["tasd-edit", "--color", "never", "x.tasd"]).unwrap();
+        assert_eq!(matches.value_of("path"), Some("x.tasd"));
+        assert!(matches.subcommand().is_none());
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{ErrorKind, Write};
+use crossterm::terminal;
+use tasd_edit::report::PacketListing;
+use tasd_edit::style::{self, strip_ansi};
+
+const HELP: &str = "[Enter] next page, [p] previous page, [g N] go to packet N, [/text] search (just / to repeat), [q] quit";
+
+/// A line of the listing: the position of its entry within `indexes`, and the line within that entry.
+type Cursor = (usize, usize);
+
+/// Prints the listing entries for the given packet indexes a screen at a time.
+///
+/// Entries are only formatted once they're about to be shown, and are paged line by line, so an INPUT_CHUNK with more
+/// runs than fit on the screen is split across pages. If stdout isn't a terminal, or everything fits on one screen, the
+/// entries are simply printed in full.
+///
+/// Returns false if stdout was closed before everything was printed (e.g. when piped into `head`), in which case
+/// nothing more should be printed.
+pub fn show(listing: &PacketListing, indexes: &[usize]) -> bool {
+    let rows = terminal::size().map(|(_, rows)| rows as usize).unwrap_or(24).saturating_sub(2).max(5);
+    if !style::stdout_is_terminal() {
+        let mut stdout = std::io::stdout().lock();
+        for &i in indexes {
+            match writeln!(stdout, "{}", listing.line(i)) {
+                Ok(()) => (),
+                Err(err) if err.kind() == ErrorKind::BrokenPipe => return false,
+                Err(err) => { eprintln!("Err: {}", err); return false },
+            }
+        }
+        return true;
+    }
+    
+    let mut pager = Pager { listing, indexes, cached: None };
+    let mut start = (0, 0);
+    let mut end = pager.print_page(start, rows);
+    if end.0 >= indexes.len() { return true }
+    
+    let mut search = String::new();
+    loop {
+        let last = if end.1 > 0 { end.0 + 1 } else { end.0 };
+        print!("{} ({}-{} of {}, h for help): ", crate::style().paint("--More--", crossterm::style::Color::DarkGrey), start.0 + 1, last, indexes.len());
+        std::io::stdout().flush().unwrap();
+        let mut command = String::new();
+        if std::io::stdin().read_line(&mut command).unwrap_or(0) == 0 { break }
+        let command = command.trim();
+        
+        let next = match command {
+            "" | "n" => {
+                if end.0 >= indexes.len() { break }
+                Some(end)
+            },
+            "p" => Some(pager.previous_page(start, rows)),
+            "q" => break,
+            "h" | "?" => { println!("{}", HELP); None },
+            _ if command.starts_with('/') => {
+                if command.len() > 1 { search = command[1..].to_lowercase() }
+                if search.is_empty() {
+                    println!("Nothing to search for.");
+                    None
+                } else {
+                    let found = pager.search(start, &search);
+                    if found.is_none() { println!("Not found: {}", search) }
+                    found
+                }
+            },
+            _ => {
+                match command.trim_start_matches('g').trim().parse::<usize>() {
+                    Ok(target) => {
+                        let pos = indexes.partition_point(|&i| i < target);
+                        if pos >= indexes.len() { println!("No packet at or after index {}.", target) }
+                        (pos < indexes.len()).then_some((pos, 0))
+                    },
+                    Err(_) => { println!("{}", HELP); None },
+                }
+            },
+        };
+        
+        if let Some(next) = next {
+            start = next;
+            end = pager.print_page(start, rows);
+        }
+    }
+    
+    true
+}
+
+struct Pager<'a> {
+    listing: &'a PacketListing<'a>,
+    indexes: &'a [usize],
+    /// Lines of the most recently formatted entry, since a large INPUT_CHUNK can span many pages.
+    cached: Option<(usize, Vec<String>)>,
+}
+impl Pager<'_> {
+    fn lines(&mut self, pos: usize) -> &[String] {
+        if self.cached.as_ref().map(|(cached, _)| *cached) != Some(pos) {
+            let mut lines: Vec<String> = self.listing.line(self.indexes[pos]).lines().map(str::to_owned).collect();
+            if lines.is_empty() { lines.push(String::new()) }
+            self.cached = Some((pos, lines));
+        }
+        
+        &self.cached.as_ref().unwrap().1
+    }
+    
+    /// Prints lines from `start` until the screen is full, returning the position after the last one printed.
+    fn print_page(&mut self, start: Cursor, rows: usize) -> Cursor {
+        let (mut pos, mut row) = start;
+        let mut used = 0;
+        while pos < self.indexes.len() && used < rows {
+            let lines = self.lines(pos);
+            let count = (lines.len() - row).min(rows - used);
+            lines[row..(row + count)].iter().for_each(|line| println!("{}", line));
+            used += count;
+            row += count;
+            if row == lines.len() {
+                pos += 1;
+                row = 0;
+            }
+        }
+        
+        (pos, row)
+    }
+    
+    /// Start of the page which ends right before `start`.
+    fn previous_page(&mut self, start: Cursor, rows: usize) -> Cursor {
+        let (mut pos, mut row) = start;
+        let mut remaining = rows;
+        while remaining > 0 {
+            if row == 0 {
+                if pos == 0 { break }
+                pos -= 1;
+                row = self.lines(pos).len();
+            }
+            let count = row.min(remaining);
+            row -= count;
+            remaining -= count;
+        }
+        
+        (pos, row)
+    }
+    
+    /// The first line after `from` containing `text` (which must be lowercase), ignoring case and colors.
+    fn search(&mut self, from: Cursor, text: &str) -> Option<Cursor> {
+        let (mut pos, mut row) = (from.0, from.1 + 1);
+        while pos < self.indexes.len() {
+            if let Some(found) = self.lines(pos).iter().skip(row).position(|line| strip_ansi(line).to_lowercase().contains(text)) {
+                return Some((pos, row + found));
+            }
+            pos += 1;
+            row = 0;
+        }
+        
+        None
+    }
+}
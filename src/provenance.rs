@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tasd::spec::*;
+use crate::raw::Header;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A new, empty file with the given header, stamped with its creation time and the version of TASD-Edit which created
+/// it.
+pub fn new_movie(source_path: PathBuf, header: Header) -> TasdMovie {
+    TasdMovie {
+        version: header.version,
+        keylen: header.keylen,
+        packets: vec![
+            Box::new(DumpCreated::new(Utc::now().timestamp())),
+            Box::new(Comment::new(format!("Created by TASD-Edit v{}", VERSION))),
+        ],
+        source_path,
+    }
+}
+
+/// Records which legacy file was imported, so a dump can be traced back to the exact file it came from.
+pub fn import_comment(path: &Path, data: &[u8]) -> Box<dyn Packet> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    Box::new(Comment::new(format!("Imported from {} (SHA-256: {:x}) by TASD-Edit v{}", name, Sha256::digest(data), VERSION)))
+}
+
+/// Whether the packet is one of the comments added by `new_movie` or `import_comment`, which only describe the file
+/// they were added to.
+pub fn is_provenance_comment(packet: &dyn Packet) -> bool {
+    packet.as_any().downcast_ref::<Comment>().is_some_and(|comment| {
+        comment.comment.starts_with("Created by TASD-Edit v") || (comment.comment.starts_with("Imported from ") && comment.comment.contains(" by TASD-Edit v"))
+    })
+}
@@ -0,0 +1,353 @@
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::stored::StoredPacket;
+use crate::unknown::UnknownPacket;
+
+/// Key given to packets whose key is too wide to be held as a 2-byte key. The spec doesn't use it, so such packets are
+/// always treated as unknown.
+pub const OVERSIZED_KEY: Key = [0x00, 0x00];
+
+/// Header values found at the start of every TASD file.
+#[derive(Clone, Copy, Debug)]
+pub struct Header {
+    pub version: u16,
+    pub keylen: u8,
+}
+impl Header {
+    /// The newest version of the spec this tool knows, with 2-byte keys.
+    pub const LATEST: Header = Header { version: u16::from_be_bytes(LATEST_VERSION), keylen: 2 };
+}
+
+/// A single packet as it appears in the file, before being parsed.
+#[derive(Clone, Copy, Debug)]
+pub struct RawPacket<'a> {
+    /// The packet's key, read as a 2-byte key whatever the file's key width is (see `key_from_bytes`), or
+    /// `OVERSIZED_KEY` if it doesn't fit in 2 bytes.
+    pub key: Key,
+    /// The entire serialized packet (key, length exponent, length, and payload), with the file's key width.
+    pub bytes: &'a [u8],
+    /// Index into `bytes` where the payload begins.
+    pub payload_start: usize,
+    /// Position of the packet within the data it was split from.
+    pub offset: usize,
+}
+impl<'a> RawPacket<'a> {
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[self.payload_start..]
+    }
+}
+
+pub fn parse_header(data: &[u8]) -> Result<Header> {
+    if data.len() < 7 || data[0..4] != MAGIC_NUMBER {
+        return Err(Error::Parse("Magic Number doesn't match TASD format.".to_owned()));
+    }
+    let header = Header {
+        version: u16::from_be_bytes([data[4], data[5]]),
+        keylen: data[6],
+    };
+    if header.keylen == 0 || header.keylen > 8 {
+        return Err(Error::Parse(format!("Unsupported key width: {}", header.keylen)));
+    }
+    
+    Ok(header)
+}
+
+/// Splits the packet region of a TASD file into individual packets, checking all lengths along the way.
+pub fn split_packets(data: &[u8]) -> Result<Vec<RawPacket<'_>>> {
+    let header = parse_header(data)?;
+    let mut packets = Vec::new();
+    
+    let mut i = 7;
+    while i < data.len() {
+        let packet = RawPacket { offset: i, ..split_packet(&data[i..], header.keylen).map_err(|err| err.context(format!("Packet at offset {:#X}", i)))? };
+        packets.push(packet);
+        i += packet.bytes.len();
+    }
+    
+    Ok(packets)
+}
+
+/// Reads one packet, whose key is `keylen` bytes wide, from the start of `data`.
+pub fn split_packet(data: &[u8], keylen: u8) -> Result<RawPacket<'_>> {
+    let keylen = keylen as usize;
+    if data.len() < keylen + 1 { return Err(Error::Parse("Truncated packet header".to_owned())) }
+    let key = key_from_bytes(&data[..keylen]).unwrap_or(OVERSIZED_KEY);
+    let exp = data[keylen] as usize;
+    if exp > 8 { return Err(Error::Parse(format!("Payload length exponent too large: {}", exp))) }
+    let start = keylen + 1 + exp;
+    if data.len() < start { return Err(Error::Parse("Truncated payload length".to_owned())) }
+    
+    let len = data[(keylen + 1)..start].iter().fold(0u64, |len, byte| (len << 8) | *byte as u64) as usize;
+    let end = start.checked_add(len).filter(|end| *end <= data.len()).ok_or_else(|| Error::Parse("Truncated payload".to_owned()))?;
+    
+    Ok(RawPacket {
+        key,
+        bytes: &data[..end],
+        payload_start: start,
+        offset: 0,
+    })
+}
+
+/// Reads a key of any width as a 2-byte key, which is how packets are held in memory.
+///
+/// Keys are big-endian numbers, so a wider key is only readable if its leading bytes are zero, and a 1-byte key is
+/// padded with a leading zero.
+pub fn key_from_bytes(bytes: &[u8]) -> Result<Key> {
+    let split = bytes.len().saturating_sub(2);
+    if bytes[..split].iter().any(|byte| *byte != 0) { return Err(Error::Parse(format!("Key {} is too large to be read", encode_key(bytes)))) }
+    
+    let mut key = [0; 2];
+    key[(2 - (bytes.len() - split))..].copy_from_slice(&bytes[split..]);
+    Ok(key)
+}
+
+/// Writes a 2-byte key with the given width. Fails if the key's value doesn't fit.
+pub fn key_to_bytes(key: Key, keylen: u8) -> Result<Vec<u8>> {
+    resize_key(&key, keylen)
+}
+
+/// Writes a key of any width with the given width. Fails if the key's value doesn't fit.
+fn resize_key(key: &[u8], keylen: u8) -> Result<Vec<u8>> {
+    let keylen = keylen as usize;
+    let significant = &key[key.iter().position(|byte| *byte != 0).unwrap_or(key.len())..];
+    if significant.len() > keylen { return Err(Error::Other(format!("Key {} doesn't fit in {} byte(s)", encode_key(key), keylen))) }
+    
+    let mut out = vec![0; keylen - significant.len()];
+    out.extend_from_slice(significant);
+    Ok(out)
+}
+
+fn encode_key(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>())
+}
+
+/// Rewrites a serialized packet with a different key width, including the packet embedded in a TRANSITION or
+/// MOVIE_TRANSITION. The length keeps its original encoding whenever it still fits, so converting back to the original
+/// width gives back the original bytes.
+pub fn rekey(packet: &[u8], from: u8, to: u8) -> Result<Vec<u8>> {
+    if packet.is_empty() || from == to { return Ok(packet.to_vec()) } // `Packet::raw()` is empty for empty payloads
+    
+    let raw = split_packet(packet, from)?;
+    let mut payload = raw.payload().to_vec();
+    let nested = match raw.key {
+        KEY_TRANSITION if payload.len() > 10 && payload[9] == 0xFF => Some(10),
+        KEY_MOVIE_TRANSITION if payload.len() > 5 && payload[4] == 0xFF => Some(5),
+        _ => None,
+    };
+    if let Some(start) = nested {
+        // Malformed embedded packets are kept as they are, like `parse_packet` keeps them as `Unsupported`
+        if split_packet(&payload[start..], from).is_ok_and(|inner| inner.bytes.len() == payload.len() - start) {
+            let inner = rekey(&payload[start..], from, to)?;
+            payload.truncate(start);
+            payload.extend(inner);
+        }
+    }
+    
+    let original_exp = packet[from as usize];
+    let exp = if original_exp >= 8 || (payload.len() as u64) < 1u64 << (8 * original_exp as u32) {
+        original_exp
+    } else {
+        ((usize::BITS - payload.len().leading_zeros()).div_ceil(8)) as u8
+    };
+    let mut out = resize_key(&packet[..from as usize], to)?;
+    out.push(exp);
+    out.extend_from_slice(&(payload.len() as u64).to_be_bytes()[(8 - exp as usize)..]);
+    out.extend(payload);
+    
+    Ok(out)
+}
+
+/// Converts the contents of a TASD file to a different key width.
+pub fn convert_keylen(data: &[u8], keylen: u8) -> Result<Vec<u8>> {
+    let header = parse_header(data)?;
+    let packets = split_packets(data)?.iter().map(|raw| rekey(raw.bytes, header.keylen, keylen)).collect::<Result<Vec<_>>>()?;
+    
+    Ok(write_file(Header { keylen, ..header }, &packets))
+}
+
+/// Serializes a packet held in memory with the given key width. Fails if its key doesn't fit.
+pub fn packet_bytes(packet: &dyn Packet, keylen: u8) -> Result<Vec<u8>> {
+    rekey(&packet.raw(), memory_keylen(packet), keylen)
+}
+
+/// The payload of a packet held in memory, whichever way its length is encoded. Packets with the same key and payload
+/// hold the same content, even if they're serialized differently (see `StoredPacket`).
+pub fn packet_payload(packet: &dyn Packet) -> Vec<u8> {
+    split_packet(&packet.raw(), memory_keylen(packet)).map(|raw| raw.payload().to_vec()).unwrap_or_default()
+}
+
+/// Key width of a packet's `Packet::raw()` output, which is 2 apart from unknown packets with oversized keys.
+fn memory_keylen(packet: &dyn Packet) -> u8 {
+    packet.as_any().downcast_ref::<UnknownPacket>().map_or(2, |unknown| unknown.keylen())
+}
+
+/// Serializes a movie using its own version and key width.
+pub fn dump(tasd: &TasdMovie) -> Result<Vec<u8>> {
+    let packets = tasd.packets.iter().map(|packet| packet_bytes(packet.as_ref(), tasd.keylen)).collect::<Result<Vec<_>>>()?;
+    
+    Ok(write_file(Header { version: tasd.version, keylen: tasd.keylen }, &packets))
+}
+
+/// Parses a packet payload into its typed representation.
+///
+/// Unlike the parsers in `tasd::spec`, this never panics. Payloads which are too short for their packet type, and
+/// keys which aren't recognized, are kept as `UnknownPacket`s so that no data is lost.
+pub fn parse_packet(key: Key, payload: &[u8]) -> Box<dyn Packet> {
+    if payload.len() < min_payload_len(key) { return Box::new(UnknownPacket::new(key, payload)) }
+    
+    match key {
+        KEY_CONSOLE_TYPE => ConsoleType::parse(key, payload),
+        KEY_CONSOLE_REGION => ConsoleRegion::parse(key, payload),
+        KEY_GAME_TITLE => GameTitle::parse(key, payload),
+        KEY_ROM_NAME => RomName::parse(key, payload),
+        KEY_ATTRIBUTION => Attribution::parse(key, payload),
+        KEY_CATEGORY => Category::parse(key, payload),
+        KEY_EMULATOR_NAME => EmulatorName::parse(key, payload),
+        KEY_EMULATOR_VERSION => EmulatorVersion::parse(key, payload),
+        KEY_EMULATOR_CORE => EmulatorCore::parse(key, payload),
+        KEY_TAS_LAST_MODIFIED if payload.len() == 8 => TasLastModified::parse(key, payload),
+        KEY_DUMP_CREATED if payload.len() == 8 => DumpCreated::parse(key, payload),
+        KEY_DUMP_LAST_MODIFIED if payload.len() == 8 => DumpLastModified::parse(key, payload),
+        KEY_TOTAL_FRAMES if payload.len() == 4 => TotalFrames::parse(key, payload),
+        KEY_RERECORDS if payload.len() == 4 => Rerecords::parse(key, payload),
+        KEY_SOURCE_LINK => SourceLink::parse(key, payload),
+        KEY_BLANK_FRAMES if payload.len() == 2 => BlankFrames::parse(key, payload),
+        KEY_VERIFIED => Verified::parse(key, payload),
+        KEY_MEMORY_INIT if payload.len() >= 5 + payload[4] as usize => MemoryInit::parse(key, payload),
+        KEY_GAME_IDENTIFIER => GameIdentifier::parse(key, payload),
+        KEY_MOVIE_LICENSE => MovieLicense::parse(key, payload),
+        KEY_MOVIE_FILE if payload.len() > payload[0] as usize => MovieFile::parse(key, payload),
+        KEY_PORT_CONTROLLER => PortController::parse(key, payload),
+        // `NesLatchFilter::parse` reads the wrong number of bytes and always panics
+        KEY_NES_LATCH_FILTER if payload.len() == 2 => Box::new(NesLatchFilter::new(u16::from_be_bytes([payload[0], payload[1]]))),
+        KEY_NES_CLOCK_FILTER => NesClockFilter::parse(key, payload),
+        KEY_NES_OVERREAD => NesOverread::parse(key, payload),
+        KEY_NES_GAME_GENIE_CODE => NesGameGenieCode::parse(key, payload),
+        KEY_SNES_CLOCK_FILTER => SnesClockFilter::parse(key, payload),
+        KEY_SNES_OVERREAD => SnesOverread::parse(key, payload),
+        KEY_SNES_GAME_GENIE_CODE => SnesGameGenieCode::parse(key, payload),
+        KEY_SNES_LATCH_TRAIN => SnesLatchTrain::parse(key, payload),
+        KEY_GENESIS_GAME_GENIE_CODE => GenesisGameGenieCode::parse(key, payload),
+        KEY_INPUT_CHUNK => InputChunk::parse(key, payload),
+        KEY_INPUT_MOMENT => InputMoment::parse(key, payload),
+        KEY_TRANSITION => match parse_nested(payload[9], &payload[10..]) {
+            Some(packet) => Box::new(Transition::new(payload[0], u64::from_be_bytes(payload[1..9].try_into().unwrap()), payload[9], packet)),
+            None => Box::new(UnknownPacket::new(key, payload)),
+        },
+        KEY_LAG_FRAME_CHUNK => LagFrameChunk::parse(key, payload),
+        KEY_MOVIE_TRANSITION => match parse_nested(payload[4], &payload[5..]) {
+            Some(packet) => Box::new(MovieTransition::new(u32::from_be_bytes(payload[0..4].try_into().unwrap()), payload[4], packet)),
+            None => Box::new(UnknownPacket::new(key, payload)),
+        },
+        KEY_COMMENT => Comment::parse(key, payload),
+        KEY_EXPERIMENTAL => Experimental::parse(key, payload),
+        KEY_UNSPECIFIED => Unspecified::parse(key, payload),
+        _ => Box::new(UnknownPacket::new(key, payload)),
+    }
+}
+
+/// Parses a packet read from a file with 2-byte keys. Unlike `parse_packet`, unknown packets keep their exact bytes, and
+/// packets which wouldn't be written back the same way (see `is_canonical`) are wrapped in a `StoredPacket`.
+pub fn parse_raw(raw: &RawPacket) -> Box<dyn Packet> {
+    let packet = parse_packet(raw.key, raw.payload());
+    if packet.as_any().is::<UnknownPacket>() { return Box::new(UnknownPacket::from_raw(raw, 2)) }
+    if !is_canonical(packet.as_ref(), raw, 2) { return Box::new(StoredPacket::new(packet, raw)) }
+    
+    packet
+}
+
+/// Parses the packet embedded in a TRANSITION or MOVIE_TRANSITION. Returns `None` if the embedded data is malformed.
+fn parse_nested(transition_kind: u8, data: &[u8]) -> Option<Option<Box<dyn Packet>>> {
+    if transition_kind != 0xFF { return Some(None) }
+    
+    let raw = split_packet(data, 2).ok()?;
+    if raw.bytes.len() != data.len() { return None }
+    
+    Some(Some(parse_packet(raw.key, raw.payload())))
+}
+
+/// Smallest payload that the packet type's parser can safely read.
+fn min_payload_len(key: Key) -> usize {
+    match key {
+        KEY_CONSOLE_TYPE | KEY_CONSOLE_REGION | KEY_ATTRIBUTION | KEY_VERIFIED | KEY_MOVIE_FILE => 1,
+        KEY_NES_CLOCK_FILTER | KEY_NES_OVERREAD | KEY_SNES_CLOCK_FILTER | KEY_SNES_OVERREAD => 1,
+        KEY_EXPERIMENTAL | KEY_INPUT_CHUNK => 1,
+        KEY_GAME_IDENTIFIER => 2,
+        KEY_PORT_CONTROLLER => 3,
+        KEY_MEMORY_INIT => 5,
+        KEY_MOVIE_TRANSITION => 5,
+        KEY_LAG_FRAME_CHUNK => 8,
+        KEY_INPUT_MOMENT | KEY_TRANSITION => 10,
+        _ => 0,
+    }
+}
+
+/// Packets whose `Packet::raw()` output differs from the bytes they were parsed from (non-minimal length encodings,
+/// invalid UTF-8, etc) can't be represented by their fields alone without changing the file. `keylen` is the key width
+/// of the file the packet came from.
+pub fn is_canonical(packet: &dyn Packet, raw: &RawPacket, keylen: u8) -> bool {
+    rekey(&packet.raw(), 2, keylen).is_ok_and(|bytes| bytes == raw.bytes)
+}
+
+/// Formats data as rows of 16 bytes, each starting with its offset (plus `base`) and ending with the bytes as ASCII.
+pub fn hex_dump(data: &[u8], base: usize) -> Vec<String> {
+    data.chunks(16).enumerate().map(|(i, row)| {
+        let hex: Vec<String> = row.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = row.iter().map(|byte| if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' }).collect();
+        format!("{:08X}  {:<47}  |{}|", base + i * 16, hex.join(" "), ascii)
+    }).collect()
+}
+
+/// Serializes a complete TASD file from a header and already-serialized packets.
+pub fn write_file(header: Header, packets: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&MAGIC_NUMBER);
+    out.extend_from_slice(&header.version.to_be_bytes());
+    out.push(header.keylen);
+    packets.iter().for_each(|packet| out.extend_from_slice(packet));
+    
+    out
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    
+    /// A small file containing the packets most likely to change when re-serialized: an empty string, a length written
+    /// with more bytes than it needs, and a key the spec doesn't define. Every key is `keylen` bytes wide (at least 2).
+    pub(crate) fn fixture(keylen: u8) -> Vec<u8> {
+        let packets: [(Key, &[u8]); 7] = [
+            (KEY_CONSOLE_TYPE, &[0x01, 0x01, 0x01]),
+            (KEY_PORT_CONTROLLER, &[0x01, 0x03, 0x01, 0x01, 0x01]),
+            (KEY_GAME_TITLE, &[0x01, 0x00]),
+            (KEY_GAME_TITLE, &[0x02, 0x00, 0x05, b'M', b'a', b'r', b'i', b'o']),
+            ([0x77, 0x77], &[0x01, 0x03, 0xAA, 0xBB, 0xCC]),
+            (KEY_INPUT_CHUNK, &[0x01, 0x04, 0x01, 0xFF, 0xFE, 0xFD]),
+            (KEY_COMMENT, &[0x01, 0x02, b'h', b'i']),
+        ];
+        
+        let packets: Vec<Vec<u8>> = packets.iter().map(|(key, rest)| {
+            let mut packet = vec![0; keylen as usize - 2];
+            packet.extend_from_slice(key);
+            packet.extend_from_slice(rest);
+            packet
+        }).collect();
+        write_file(Header { version: 1, keylen }, &packets)
+    }
+    
+    #[test]
+    fn key_width_round_trip_is_exact() {
+        assert_eq!(convert_keylen(&fixture(2), 4).unwrap(), fixture(4));
+        assert_eq!(convert_keylen(&fixture(4), 2).unwrap(), fixture(2));
+        assert_eq!(convert_keylen(&convert_keylen(&fixture(2), 8).unwrap(), 2).unwrap(), fixture(2));
+    }
+    
+    #[test]
+    fn narrowing_names_the_key_that_doesnt_fit() {
+        let packet = [0x00, 0x01, 0x00, 0x05, 0x01, 0x00];
+        assert_eq!(split_packet(&packet, 4).unwrap().key, OVERSIZED_KEY);
+        assert_eq!(rekey(&packet, 4, 8).unwrap(), [&[0; 4][..], &packet].concat());
+        assert_eq!(rekey(&packet, 4, 2).unwrap_err().to_string(), "Key 0x00010005 doesn't fit in 2 byte(s)");
+        assert!(rekey(&[0x01, 0x00, 0x01, 0x00], 2, 1).is_err());
+    }
+}
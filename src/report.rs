@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use crossterm::style::Color;
+use tasd::lookup::input_moment_lut;
+use tasd::spec::*;
+use crate::fields::{encode_hex, key_name, UNKNOWN_NAME};
+use crate::input::{decode_frame, decode_runs, frame_size, port_controllers};
+use crate::raw::hex_dump;
+use crate::style::Style;
+use crate::unknown::UnknownPacket;
+
+/// Most payload bytes of an unknown packet shown in a listing.
+const UNKNOWN_DUMP_LIMIT: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Plain,
+    Markdown,
+    Html,
+}
+impl ReportFormat {
+    pub const ALL: [ReportFormat; 3] = [ReportFormat::Plain, ReportFormat::Markdown, ReportFormat::Html];
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportFormat::Plain => "Plain text",
+            ReportFormat::Markdown => "Markdown",
+            ReportFormat::Html => "HTML",
+        }
+    }
+    
+    /// Extension appended to the source file name when no other path is chosen.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Plain => "tasd.pretty.txt",
+            ReportFormat::Markdown => "tasd.pretty.md",
+            ReportFormat::Html => "tasd.pretty.html",
+        }
+    }
+}
+
+/// Listing of every packet, one per line, preceded by the file's header information.
+///
+/// Input packets are decoded for the port's controller type. Each INPUT_CHUNK is followed by its frames, with runs of
+/// identical frames collapsed into a single line. Unknown packets show their key followed by a hex dump of the payload.
+///
+/// Lines are formatted one at a time as they're requested, so that huge files don't need to be formatted all at once.
+pub struct PacketListing<'a> {
+    tasd: &'a TasdMovie,
+    controllers: HashMap<u8, u16>,
+    /// First frame number of each INPUT_CHUNK, by packet index.
+    chunk_frames: HashMap<usize, usize>,
+    padding: usize,
+    style: Style,
+}
+impl<'a> PacketListing<'a> {
+    pub fn new(tasd: &'a TasdMovie, style: Style) -> Self {
+        let controllers = port_controllers(&tasd.packets);
+        let mut next_frame: HashMap<u8, usize> = HashMap::new();
+        let mut chunk_frames = HashMap::new();
+        for (i, packet) in tasd.packets.iter().enumerate() {
+            if let Some(chunk) = packet.as_any().downcast_ref::<InputChunk>() {
+                let first = next_frame.entry(chunk.port).or_insert(0);
+                chunk_frames.insert(i, *first);
+                *first += chunk.inputs.chunks(controllers.get(&chunk.port).copied().map(frame_size).unwrap_or(1)).count();
+            }
+        }
+        
+        Self {
+            tasd,
+            controllers,
+            chunk_frames,
+            padding: tasd.packets.len().saturating_sub(1).to_string().len(),
+            style,
+        }
+    }
+    
+    pub fn header(&self) -> String {
+        format!("Version: {:#06X}, Key Width: {}", self.tasd.version, self.tasd.keylen)
+    }
+    
+    /// The entry for packet `i`. INPUT_CHUNK entries span multiple lines.
+    pub fn line(&self, i: usize) -> String {
+        let packet = &self.tasd.packets[i];
+        let (padding, style) = (self.padding, self.style);
+        let index = style.paint(format!("{:padding$}", i, padding=padding), Color::Cyan);
+        let any = packet.as_any();
+        if let Some(chunk) = any.downcast_ref::<InputChunk>() {
+            let kind = self.controllers.get(&chunk.port).copied();
+            let first = self.chunk_frames[&i];
+            let runs = decode_runs(kind, &chunk.inputs, first);
+            let count = chunk.inputs.chunks(kind.map(frame_size).unwrap_or(1)).count();
+            
+            let mut text = match count {
+                0 => format!("[{}]: {} Port #{}, no frames", index, style.paint("INPUT_CHUNK", Color::DarkYellow), chunk.port),
+                _ => format!("[{}]: {} Port #{}, frames {}-{}", index, style.paint("INPUT_CHUNK", Color::DarkYellow), chunk.port, first, first + count - 1),
+            };
+            let ranges: Vec<String> = runs.iter().map(|(first, last, _)| if first == last { first.to_string() } else { format!("{}-{}", first, last) }).collect();
+            let width = ranges.iter().map(|range| range.len()).max().unwrap_or(0);
+            for (range, (_, _, frame)) in ranges.iter().zip(&runs) {
+                text.push_str(&format!("\n{:indent$}{:>width$}: {}", "", range, frame, indent=padding + 4, width=width));
+            }
+            text
+        } else if let Some(moment) = any.downcast_ref::<InputMoment>() {
+            let kind = self.controllers.get(&moment.port).copied();
+            format!("[{}]: {} Port #{}, {} {}: {}", index, style.paint("INPUT_MOMENT", Color::DarkYellow), moment.port, input_moment_lut(moment.kind).unwrap_or("Unknown"), moment.index, decode_frame(kind, &moment.inputs))
+        } else if let Some(unknown) = any.downcast_ref::<UnknownPacket>() {
+            let payload = unknown.payload();
+            let name = style.paint(key_name(unknown.key).unwrap_or(UNKNOWN_NAME), Color::DarkYellow);
+            let mut text = format!("[{}]: {} key 0x{}, {} byte payload{}", index, name, encode_hex(unknown.key_bytes()), payload.len(), if unknown.is_known_key() { " which couldn't be read" } else { "" });
+            for line in hex_dump(&payload[..payload.len().min(UNKNOWN_DUMP_LIMIT)], 0) {
+                text.push_str(&format!("\n{:indent$}{}", "", line, indent=padding + 4));
+            }
+            if payload.len() > UNKNOWN_DUMP_LIMIT {
+                text.push_str(&format!("\n{:indent$}... {} more bytes", "", payload.len() - UNKNOWN_DUMP_LIMIT, indent=padding + 4));
+            }
+            text
+        } else {
+            format!("[{}]: {}", index, style.packet(packet.as_ref()))
+        }
+    }
+}
+
+/// Renders the same listing as `PacketListing`, without any terminal escape codes.
+pub fn render(tasd: &TasdMovie, format: ReportFormat) -> String {
+    let title = tasd.source_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let header = format!("Version: {:#06X}, Key Width: {}", tasd.version, tasd.keylen);
+    let listing = PacketListing::new(tasd, Style::PLAIN);
+    // Each row holds the listing's entry for the packet, split into its first line (after the name) and any further lines,
+    // such as the decoded frames of an INPUT_CHUNK
+    let rows: Vec<(usize, &str, Vec<String>)> = tasd.packets.iter().enumerate().map(|(i, packet)| {
+        let name = key_name(packet.key()).unwrap_or(UNKNOWN_NAME);
+        let text = listing.line(i);
+        let mut lines: Vec<String> = text.lines().map(|line| line.trim().to_owned()).collect();
+        let first = lines[0].split_once("]: ").map_or(lines[0].as_str(), |(_, rest)| rest);
+        lines[0] = first.strip_prefix(name).unwrap_or(first).trim().to_owned();
+        (i, name, lines)
+    }).collect();
+    
+    match format {
+        ReportFormat::Plain => {
+            let mut out = format!("{}\n", listing.header());
+            for i in 0..tasd.packets.len() {
+                out.push_str(&listing.line(i));
+                out.push('\n');
+            }
+            out
+        },
+        ReportFormat::Markdown => {
+            let mut out = format!("# {}\n\n{}\n\n| Index | Packet | Details |\n|------:|--------|---------|\n", escape_markdown(&title), header);
+            for (i, name, details) in rows {
+                let details: Vec<String> = details.iter().map(|line| escape_markdown(line)).collect();
+                out.push_str(&format!("| {} | `{}` | {} |\n", i, name, details.join("<br>")));
+            }
+            out
+        },
+        ReportFormat::Html => {
+            let mut out = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n<p>{1}</p>\n<table>\n<tr><th>Index</th><th>Packet</th><th>Details</th></tr>\n", escape_html(&title), header);
+            for (i, name, details) in rows {
+                let details: Vec<String> = details.iter().map(|line| escape_html(line)).collect();
+                out.push_str(&format!("<tr><td>{}</td><td><code>{}</code></td><td>{}</td></tr>\n", i, name, details.join("<br>")));
+            }
+            out.push_str("</table>\n</body>\n</html>\n");
+            out
+        },
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        if "\\`*_[]<>|".contains(c) { out.push('\\') }
+        out.push(c);
+    }
+    
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    
+    fn movie() -> TasdMovie {
+        TasdMovie {
+            version: 1,
+            keylen: 2,
+            packets: vec![
+                Box::new(PortController::new(1, 0x0101)),
+                Box::new(InputChunk::new(1, vec![0x7F, 0x7F, 0xFF])),
+            ],
+            source_path: PathBuf::from("movie.tasd"),
+        }
+    }
+    
+    #[test]
+    fn every_format_decodes_frames() {
+        let tasd = movie();
+        assert!(render(&tasd, ReportFormat::Plain).contains("     0-1: A.......\n       2: ........\n"));
+        assert!(render(&tasd, ReportFormat::Markdown).contains("| 1 | `INPUT_CHUNK` | Port #1, frames 0-2<br>0-1: A.......<br>2: ........ |"));
+        assert!(render(&tasd, ReportFormat::Html).contains("<td>Port #1, frames 0-2<br>0-1: A.......<br>2: ........</td>"));
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::Path;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tasd::spec::*;
+
+/// GameIdentifier types (see `game_identifier_lut`) which can be computed from a ROM file.
+pub const HASH_KINDS: [u8; 4] = [0x03, 0x04, 0x05, 0x07];
+
+/// Computes the GameIdentifier of the given type, if supported. Hashes are returned as raw bytes.
+pub fn hash(kind: u8, data: &[u8]) -> Option<Vec<u8>> {
+    Some(match kind {
+        0x03 => crc32fast::hash(data).to_be_bytes().to_vec(),
+        0x04 => Md5::digest(data).to_vec(),
+        0x05 => Sha1::digest(data).to_vec(),
+        0x07 => Sha256::digest(data).to_vec(),
+        _ => return None,
+    })
+}
+
+/// Removes any header which isn't part of the ROM itself, returning the remaining data and the kind of header removed.
+///
+/// ROM databases (such as No-Intro) hash NES and FDS images without their iNES/fwNES header, and SNES images without
+/// the 512 byte header added by copier devices, so the same is done here for hashes to match.
+pub fn strip_header<'a>(path: &Path, data: &'a [u8]) -> (&'a [u8], Option<&'static str>) {
+    let extension = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+    
+    if data.len() >= 16 && data.starts_with(b"NES\x1A") {
+        (&data[16..], Some("iNES header"))
+    } else if data.len() >= 16 && data.starts_with(b"FDS\x1A") {
+        (&data[16..], Some("fwNES header"))
+    } else if ["smc", "sfc", "swc", "fig"].contains(&extension.as_str()) && data.len() % 1024 == 512 {
+        (&data[512..], Some("SMC copier header"))
+    } else {
+        (data, None)
+    }
+}
+
+/// Metadata found in a ROM's internal header.
+pub struct RomInfo {
+    /// See `console_type_lut`.
+    pub console_type: u8,
+    /// See `console_region_lut`. Only set for home consoles whose header specifies a region.
+    pub region: Option<u8>,
+    pub title: Option<String>,
+    /// Other notable header fields, shown to the user for reference.
+    pub details: Vec<String>,
+}
+
+/// Identifies the console a ROM is for from its header, and reads what metadata it can.
+///
+/// Supports iNES/NES 2.0, SNES (LoROM and HiROM), GB/GBC, GBA, and Genesis (non-interleaved) images.
+pub fn read_header(path: &Path, data: &[u8]) -> Option<RomInfo> {
+    if data.len() >= 16 && data.starts_with(b"NES\x1A") { return Some(read_ines(data)) }
+    if data.len() >= 0xC0 && data[0xB2] == 0x96 && data[0x04..0x08] == [0x24, 0xFF, 0xAE, 0x51] { return Some(read_gba(data)) }
+    if data.len() >= 0x150 && data[0x104..0x108] == [0xCE, 0xED, 0x66, 0x66] { return Some(read_gb(data)) }
+    if data.len() >= 0x200 && &data[0x100..0x104] == b"SEGA" { return Some(read_genesis(data)) }
+    
+    read_snes(strip_header(path, data).0)
+}
+
+fn read_ines(data: &[u8]) -> RomInfo {
+    let nes2 = data[7] & 0x0C == 0x08;
+    let mut mapper = ((data[6] >> 4) | (data[7] & 0xF0)) as u16;
+    let region = if nes2 {
+        mapper |= ((data[8] & 0x0F) as u16) << 8;
+        match data[12] & 0x03 {
+            0 => Some(0x01),
+            1 => Some(0x02),
+            _ => None, // multi-region or Dendy
+        }
+    } else {
+        // rarely set correctly in iNES 1.0 headers, so a PAL flag is the only thing trusted
+        if data[9] & 0x01 == 1 { Some(0x02) } else { None }
+    };
+    
+    RomInfo {
+        console_type: 0x01,
+        region,
+        title: None,
+        details: vec![
+            format!("Header format: {}", if nes2 { "NES 2.0" } else { "iNES" }),
+            format!("Mapper: {}", mapper),
+        ],
+    }
+}
+
+fn read_snes(data: &[u8]) -> Option<RomInfo> {
+    // the internal header sits at the end of the first bank, which is 32KiB for LoROM and 64KiB for HiROM
+    let (offset, layout) = [(0x7FC0, "LoROM"), (0xFFC0, "HiROM")].into_iter()
+        .map(|(offset, layout)| (snes_header_score(data, offset, layout == "HiROM"), offset, layout))
+        .filter(|(score, _, _)| *score >= 2)
+        .max_by_key(|(score, _, _)| *score)
+        .map(|(_, offset, layout)| (offset, layout))?;
+    let header = &data[offset..(offset + 0x20)];
+    
+    let region = match header[0x19] {
+        0x02..=0x0C | 0x11 => 0x02,
+        _ => 0x01,
+    };
+    
+    Some(RomInfo {
+        console_type: 0x02,
+        region: Some(region),
+        title: header_string(&header[0x00..0x15]),
+        details: vec![
+            format!("Memory map: {}", layout),
+            format!("Region code: {:#04X}", header[0x19]),
+        ],
+    })
+}
+
+/// How likely it is that a valid SNES header exists at the given offset.
+fn snes_header_score(data: &[u8], offset: usize, hirom: bool) -> u8 {
+    if data.len() < offset + 0x20 { return 0 }
+    let header = &data[offset..(offset + 0x20)];
+    
+    let mut score = 0;
+    let complement = u16::from_le_bytes([header[0x1C], header[0x1D]]);
+    let checksum = u16::from_le_bytes([header[0x1E], header[0x1F]]);
+    if complement ^ checksum == 0xFFFF { score += 2 }
+    if header[0x15] & 0x01 == hirom as u8 && header[0x15] & 0xE0 == 0x20 { score += 1 }
+    if header[0x00..0x15].iter().all(|c| (0x20..0x7F).contains(c)) { score += 1 }
+    
+    score
+}
+
+fn read_gb(data: &[u8]) -> RomInfo {
+    let cgb = data[0x143];
+    let title = if cgb & 0x80 != 0 { &data[0x134..0x143] } else { &data[0x134..0x144] };
+    let licensee = match data[0x14B] {
+        0x33 => String::from_utf8_lossy(&data[0x144..0x146]).to_string(),
+        code => format!("{:02X}", code),
+    };
+    
+    RomInfo {
+        console_type: if cgb & 0x80 != 0 { 0x06 } else { 0x05 },
+        region: None,
+        title: header_string(title),
+        details: vec![
+            format!("Color support: {}", match cgb { 0xC0 => "GBC only", 0x80 => "GBC enhanced (also runs on GB)", _ => "None" }),
+            format!("Licensee: {}", licensee),
+        ],
+    }
+}
+
+fn read_gba(data: &[u8]) -> RomInfo {
+    RomInfo {
+        console_type: 0x07,
+        region: None,
+        title: header_string(&data[0xA0..0xAC]),
+        details: vec![
+            format!("Game code: {}", String::from_utf8_lossy(&data[0xAC..0xB0])),
+            format!("Maker code: {}", String::from_utf8_lossy(&data[0xB0..0xB2])),
+        ],
+    }
+}
+
+fn read_genesis(data: &[u8]) -> RomInfo {
+    let domestic = header_string(&data[0x120..0x150]);
+    let overseas = header_string(&data[0x150..0x180]);
+    let regions = String::from_utf8_lossy(&data[0x1F0..0x1F3]).trim().to_owned();
+    
+    // older carts list region letters, newer ones a single hex digit of flags (bits 0/2 are 60Hz, 1/3 are 50Hz)
+    let (ntsc, pal) = match u8::from_str_radix(&regions, 16) {
+        Ok(flags) if regions.len() == 1 && regions != "E" => (flags & 0b0101 != 0, flags & 0b1010 != 0),
+        _ => (regions.contains('J') || regions.contains('U'), regions.contains('E')),
+    };
+    
+    let mut details = Vec::new();
+    if let Some(name) = &domestic { details.push(format!("Domestic name: {}", name)) }
+    if let Some(name) = &overseas { details.push(format!("Overseas name: {}", name)) }
+    details.push(format!("Regions: {}", regions));
+    
+    RomInfo {
+        console_type: 0x08,
+        region: if ntsc { Some(0x01) } else if pal { Some(0x02) } else { None },
+        title: overseas.or(domestic),
+        details,
+    }
+}
+
+/// Metadata packets describing the ROM, for the user to review before adding them to a file.
+pub fn proposed_packets(info: &RomInfo, path: &Path) -> Vec<Box<dyn Packet>> {
+    let mut packets: Vec<Box<dyn Packet>> = vec![Box::new(ConsoleType::new(info.console_type, None))];
+    if let Some(region) = info.region { packets.push(Box::new(ConsoleRegion::new(region))) }
+    if let Some(title) = &info.title { packets.push(Box::new(GameTitle::new(title.clone()))) }
+    if let Some(name) = path.file_name() { packets.push(Box::new(RomName::new(name.to_string_lossy().to_string()))) }
+    
+    packets
+}
+
+/// Reads a fixed-width text field, stopping at the first NUL and collapsing runs of padding spaces.
+fn header_string(data: &[u8]) -> Option<String> {
+    let end = data.iter().position(|c| *c == 0).unwrap_or(data.len());
+    let text: String = data[..end].iter().map(|c| if (0x20..0x7F).contains(c) { *c as char } else { ' ' }).collect();
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    
+    if text.is_empty() { None } else { Some(text) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn hashes_are_raw_bytes() {
+        assert_eq!(hash(0x03, b"123456789").unwrap(), [0xCB, 0xF4, 0x39, 0x26]);
+        assert_eq!(hash(0x07, b"").unwrap()[..4], [0xE3, 0xB0, 0xC4, 0x42]);
+        assert!(hash(0x01, b"").is_none());
+    }
+    
+    #[test]
+    fn strips_ines_and_fwnes_headers() {
+        let mut data = b"NES\x1A".to_vec();
+        data.resize(16, 0);
+        data.extend_from_slice(&[0xAA; 32]);
+        assert_eq!(strip_header(Path::new("game.nes"), &data), (&[0xAA; 32][..], Some("iNES header")));
+        
+        data[..4].copy_from_slice(b"FDS\x1A");
+        assert_eq!(strip_header(Path::new("game.fds"), &data), (&[0xAA; 32][..], Some("fwNES header")));
+    }
+    
+    #[test]
+    fn strips_smc_headers_by_size_and_extension() {
+        let data = vec![0; 512 + 1024];
+        assert_eq!(strip_header(Path::new("game.SMC"), &data), (&data[512..], Some("SMC copier header")));
+        assert_eq!(strip_header(Path::new("game.bin"), &data), (&data[..], None));
+        assert_eq!(strip_header(Path::new("game.sfc"), &data[512..]), (&data[512..], None));
+    }
+    
+    #[test]
+    fn reads_nes_2_0_headers() {
+        let mut data = b"NES\x1A".to_vec();
+        data.resize(16, 0);
+        data[6] = 0x10;
+        data[7] = 0x08;
+        data[8] = 0x01;
+        data[12] = 0x01;
+        
+        let info = read_header(Path::new("game.nes"), &data).unwrap();
+        assert_eq!((info.console_type, info.region), (0x01, Some(0x02)));
+        assert_eq!(info.details, ["Header format: NES 2.0", "Mapper: 257"]);
+    }
+    
+    #[test]
+    fn reads_snes_headers_behind_a_copier_header() {
+        let mut data = vec![0; 512 + 0x8000];
+        let header = &mut data[(512 + 0x7FC0)..(512 + 0x7FE0)];
+        header[..21].copy_from_slice(b"SUPER GAME           ");
+        header[0x15] = 0x20;
+        header[0x19] = 0x02;
+        header[0x1C..0x20].copy_from_slice(&[0xCB, 0xED, 0x34, 0x12]);
+        
+        let info = read_header(Path::new("game.smc"), &data).unwrap();
+        assert_eq!((info.console_type, info.region, info.title.as_deref()), (0x02, Some(0x02), Some("SUPER GAME")));
+        assert_eq!(info.details[0], "Memory map: LoROM");
+    }
+    
+    #[test]
+    fn reads_handheld_headers() {
+        let mut gb = vec![0; 0x150];
+        gb[0x104..0x108].copy_from_slice(&[0xCE, 0xED, 0x66, 0x66]);
+        gb[0x134..0x13A].copy_from_slice(b"TETRIS");
+        gb[0x143] = 0x80;
+        let info = read_header(Path::new("game.gbc"), &gb).unwrap();
+        assert_eq!((info.console_type, info.region, info.title.as_deref()), (0x06, None, Some("TETRIS")));
+        
+        let mut gba = vec![0; 0xC0];
+        gba[0x04..0x08].copy_from_slice(&[0x24, 0xFF, 0xAE, 0x51]);
+        gba[0xA0..0xA5].copy_from_slice(b"METRO");
+        gba[0xB2] = 0x96;
+        let info = read_header(Path::new("game.gba"), &gba).unwrap();
+        assert_eq!((info.console_type, info.title.as_deref()), (0x07, Some("METRO")));
+    }
+    
+    #[test]
+    fn reads_genesis_region_letters_and_flags() {
+        let mut data = vec![b' '; 0x200];
+        data[0x100..0x104].copy_from_slice(b"SEGA");
+        data[0x150..0x155].copy_from_slice(b"SONIC");
+        data[0x1F0..0x1F3].copy_from_slice(b"E  ");
+        let info = read_header(Path::new("game.md"), &data).unwrap();
+        assert_eq!((info.console_type, info.region, info.title.as_deref()), (0x08, Some(0x02), Some("SONIC")));
+        
+        data[0x1F0] = b'4';
+        assert_eq!(read_header(Path::new("game.md"), &data).unwrap().region, Some(0x01));
+    }
+    
+    #[test]
+    fn unrecognized_data_has_no_header() {
+        assert!(read_header(Path::new("game.bin"), &[0; 0x10000]).is_none());
+    }
+}
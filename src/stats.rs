@@ -0,0 +1,293 @@
+use std::collections::{BTreeSet, HashMap};
+use tasd::lookup::{console_region_lut, console_type_lut, controller_type_lut};
+use tasd::spec::*;
+use crate::input::{controller_count, frame_size, is_idle, port_controllers, port_inputs, pressed_buttons};
+
+/// Number of buttons listed under "Most pressed" for each port.
+const TOP_BUTTONS: usize = 5;
+
+pub struct MovieStats {
+    pub ports: Vec<PortStats>,
+    pub input_moments: usize,
+    pub lag_frames: u64,
+    pub lag_chunks: usize,
+    pub transitions: usize,
+    pub duration: Option<Duration>,
+}
+
+pub struct PortStats {
+    pub port: u8,
+    pub controller: Option<u16>,
+    /// Frames of INPUT_CHUNK data. Ports which only have INPUT_MOMENTs span up to their last moment, or 0 if its index
+    /// can't be converted to frames.
+    pub frames: usize,
+    /// Frames in which anything is pressed or moved. Counts moments instead for ports which only have INPUT_MOMENTs.
+    pub active_frames: usize,
+    pub moments: usize,
+    /// Whether the port's inputs are all INPUT_MOMENTs, as in GB, GBC, and GBA dumps.
+    pub moments_only: bool,
+    /// Number of frames (or moments) each button is held, most pressed first.
+    pub buttons: Vec<(String, usize)>,
+    /// Inclusive range of the longest run of idle frames.
+    pub longest_idle: Option<(usize, usize)>,
+}
+
+/// Expected real-time length of the movie.
+pub struct Duration {
+    pub frames: u64,
+    pub frame_rate: f64,
+    /// Describes where the frame rate came from, e.g. `NES NTSC`.
+    pub source: String,
+    /// Whether `frames` came from a TOTAL_FRAMES packet instead of being counted from the inputs.
+    pub from_total_frames: bool,
+}
+impl Duration {
+    pub fn seconds(&self) -> f64 {
+        self.frames as f64 / self.frame_rate
+    }
+}
+
+impl MovieStats {
+    /// Human-readable report, one line per element.
+    pub fn pretty(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        
+        if self.ports.is_empty() { out.push("No INPUT_CHUNK or INPUT_MOMENT packets.".to_owned()) }
+        for port in &self.ports {
+            let controller = port.controller.map(|kind| controller_type_lut(kind).unwrap_or("Unknown controller")).unwrap_or("no PORT_CONTROLLER");
+            if port.moments_only {
+                let span = if port.frames > 0 { format!(", over {} frames", port.frames) } else { String::new() };
+                out.push(format!("Port #{} ({}): {} input moments, {} with input{}", port.port, controller, port.moments, port.active_frames, span));
+            } else {
+                out.push(format!("Port #{} ({}): {} frames, {} with input", port.port, controller, port.frames, port.active_frames));
+                if port.moments > 0 { out.push(format!("  Input moments: {}", port.moments)) }
+            }
+            if !port.buttons.is_empty() {
+                let buttons: Vec<String> = port.buttons.iter().take(TOP_BUTTONS).map(|(name, count)| format!("{} {}", name, count)).collect();
+                out.push(format!("  Most pressed: {}", buttons.join(", ")));
+            }
+            if let Some((start, end)) = port.longest_idle {
+                out.push(format!("  Longest idle stretch: {} frames ({}-{})", end - start + 1, start, end));
+            }
+        }
+        
+        out.push(format!("Lag frames: {} (in {} LAG_FRAME_CHUNK packets)", self.lag_frames, self.lag_chunks));
+        out.push(format!("Transitions: {}", self.transitions));
+        match &self.duration {
+            Some(duration) => out.push(format!("Duration: {} ({} frames{} at {} fps, {})",
+                format_duration(duration.seconds()), duration.frames, if duration.from_total_frames { " from TOTAL_FRAMES" } else { "" }, duration.frame_rate, duration.source)),
+            None => out.push("Duration: unknown (add a CONSOLE_TYPE or CONSOLE_REGION packet)".to_owned()),
+        }
+        
+        out
+    }
+}
+
+pub fn movie_stats(tasd: &TasdMovie) -> MovieStats {
+    let controllers = port_controllers(&tasd.packets);
+    let chunks = tasd.search_by_key(vec![KEY_INPUT_CHUNK]);
+    let moments = tasd.search_by_key(vec![KEY_INPUT_MOMENT]);
+    let moments: Vec<&InputMoment> = moments.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputMoment>()).collect();
+    let chunk_ports: BTreeSet<u8> = chunks.iter().filter_map(|packet| packet.as_any().downcast_ref::<InputChunk>()).map(|chunk| chunk.port).collect();
+    let ports: BTreeSet<u8> = chunk_ports.iter().copied().chain(moments.iter().map(|moment| moment.port)).collect();
+    
+    let total_frames = tasd.search_by_key(vec![KEY_TOTAL_FRAMES]).iter().find_map(|packet| packet.as_any().downcast_ref::<TotalFrames>()).map(|packet| packet.frames);
+    let console = tasd.search_by_key(vec![KEY_CONSOLE_TYPE]).iter().find_map(|packet| packet.as_any().downcast_ref::<ConsoleType>()).map(|packet| packet.kind);
+    let region = tasd.search_by_key(vec![KEY_CONSOLE_REGION]).iter().find_map(|packet| packet.as_any().downcast_ref::<ConsoleRegion>()).map(|packet| packet.region);
+    let rate = frame_rate(console, region);
+    
+    let ports: Vec<PortStats> = ports.into_iter().map(|port| {
+        let controller = controllers.get(&port).copied();
+        let port_moments: Vec<&InputMoment> = moments.iter().copied().filter(|moment| moment.port == port).collect();
+        let mut stats = if chunk_ports.contains(&port) {
+            port_stats(port, controller, &port_inputs(tasd, port))
+        } else {
+            moment_stats(port, controller, &port_moments, console, rate.as_ref().map(|(rate, _)| *rate))
+        };
+        stats.moments = port_moments.len();
+        stats
+    }).collect();
+    
+    let lag = tasd.search_by_key(vec![KEY_LAG_FRAME_CHUNK]);
+    let lag_frames = lag.iter().filter_map(|packet| packet.as_any().downcast_ref::<LagFrameChunk>()).map(|chunk| chunk.count as u64).sum();
+    
+    let duration = rate.map(|(frame_rate, source)| Duration {
+        frames: total_frames.map(|frames| frames as u64).unwrap_or_else(|| ports.iter().map(|port| port.frames).max().unwrap_or(0) as u64 + lag_frames),
+        frame_rate,
+        source,
+        from_total_frames: total_frames.is_some(),
+    });
+    
+    MovieStats {
+        input_moments: moments.len(),
+        lag_frames,
+        lag_chunks: lag.len(),
+        transitions: tasd.search_by_key(vec![KEY_TRANSITION, KEY_MOVIE_TRANSITION]).len(),
+        duration,
+        ports,
+    }
+}
+
+fn port_stats(port: u8, controller: Option<u16>, inputs: &[u8]) -> PortStats {
+    let multiple = controller.map(controller_count).unwrap_or(1) > 1;
+    let mut buttons: HashMap<String, usize> = HashMap::new();
+    let mut frames = 0;
+    let mut active_frames = 0;
+    let mut idle_start = None;
+    let mut longest_idle: Option<(usize, usize)> = None;
+    
+    for (i, frame) in inputs.chunks(controller.map(frame_size).unwrap_or(1)).enumerate() {
+        frames += 1;
+        for (number, button) in pressed_buttons(controller, frame) {
+            let name = if multiple { format!("P{} {}", number + 1, button) } else { button.to_string() };
+            *buttons.entry(name).or_default() += 1;
+        }
+        
+        if is_idle(controller, frame) {
+            let start = *idle_start.get_or_insert(i);
+            if longest_idle.is_none_or(|(first, last)| i - start > last - first) { longest_idle = Some((start, i)) }
+        } else {
+            active_frames += 1;
+            idle_start = None;
+        }
+    }
+    
+    let mut buttons: Vec<(String, usize)> = buttons.into_iter().collect();
+    buttons.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    
+    PortStats { port, controller, frames, active_frames, moments: 0, moments_only: false, buttons, longest_idle }
+}
+
+/// Stats of a port whose inputs are all INPUT_MOMENTs. Each moment is counted once, however long its inputs are held.
+fn moment_stats(port: u8, controller: Option<u16>, moments: &[&InputMoment], console: Option<u8>, frame_rate: Option<f64>) -> PortStats {
+    let multiple = controller.map(controller_count).unwrap_or(1) > 1;
+    let mut buttons: HashMap<String, usize> = HashMap::new();
+    let mut active_frames = 0;
+    for moment in moments {
+        for (number, button) in pressed_buttons(controller, &moment.inputs) {
+            let name = if multiple { format!("P{} {}", number + 1, button) } else { button.to_string() };
+            *buttons.entry(name).or_default() += 1;
+        }
+        if !is_idle(controller, &moment.inputs) { active_frames += 1 }
+    }
+    
+    let mut buttons: Vec<(String, usize)> = buttons.into_iter().collect();
+    buttons.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let frames = moments.iter().filter_map(|moment| moment_frame(moment, console, frame_rate?)).max().map_or(0, |frame| frame as usize + 1);
+    
+    PortStats { port, controller, frames, active_frames, moments: moments.len(), moments_only: true, buttons, longest_idle: None }
+}
+
+/// Frame an INPUT_MOMENT happens on, based on its index and the kind of index.
+fn moment_frame(moment: &InputMoment, console: Option<u8>, frame_rate: f64) -> Option<u64> {
+    let seconds = match moment.kind {
+        0x01 => return Some(moment.index),
+        0x02 => moment.index as f64 / clock_rate(console)?,
+        0x03 => moment.index as f64 / 1000.0,
+        0x04 => moment.index as f64 / 100_000.0,
+        _ => return None,
+    };
+    
+    Some((seconds * frame_rate) as u64)
+}
+
+/// CPU clock speed in Hz, which cycle count indexes are measured in. Only known for the handhelds, whose dumps are made
+/// of INPUT_MOMENTs.
+fn clock_rate(console: Option<u8>) -> Option<f64> {
+    match console {
+        Some(0x05) | Some(0x06) => Some(4_194_304.0),
+        Some(0x07) => Some(16_777_216.0),
+        _ => None,
+    }
+}
+
+/// Frames per second of the console and region, along with a description of which were used.
+///
+/// Home consoles without a CONSOLE_REGION are assumed to be NTSC. Handhelds run at the same rate in every region.
+fn frame_rate(console: Option<u8>, region: Option<u8>) -> Option<(f64, String)> {
+    let pal = region == Some(0x02);
+    let rate = match console {
+        Some(0x01) | Some(0x02) => if pal { 50.0070 } else { 60.0988 },
+        Some(0x03) => if pal { 50.0 } else { 60.0 },
+        Some(0x05..=0x07) => return Some((59.7275, console_type_lut(console.unwrap()).unwrap().to_owned())),
+        Some(0x08) => if pal { 49.7015 } else { 59.9227 },
+        Some(0x09) => if pal { 49.8607 } else { 59.9227 },
+        _ => match region {
+            Some(0x01) => 60.0,
+            Some(0x02) => 50.0,
+            _ => return None,
+        },
+    };
+    
+    let console = console.and_then(console_type_lut).filter(|_| console != Some(0xFF));
+    let region = match region.and_then(console_region_lut) {
+        Some(region) => region.to_owned(),
+        None => "NTSC, assumed since there's no CONSOLE_REGION".to_owned(),
+    };
+    
+    Some((rate, match console {
+        Some(console) => format!("{} {}", console, region),
+        None => region,
+    }))
+}
+
+fn format_duration(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!("{}:{:02}:{:02}.{:03}", millis / 3_600_000, (millis / 60_000) % 60, (millis / 1000) % 60, millis % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    
+    fn movie(packets: Vec<Box<dyn Packet>>) -> TasdMovie {
+        TasdMovie { version: 1, keylen: 2, packets, source_path: PathBuf::from("movie.tasd") }
+    }
+    
+    #[test]
+    fn counts_chunk_frames_and_buttons() {
+        let stats = movie_stats(&movie(vec![
+            Box::new(ConsoleType::new(0x01, None)),
+            Box::new(PortController::new(1, 0x0101)),
+            Box::new(InputChunk::new(1, vec![0x7F, 0xFF, 0xFF, 0xFF, 0x7E])),
+            Box::new(LagFrameChunk::new(5, 3)),
+        ]));
+        let port = &stats.ports[0];
+        assert_eq!((port.frames, port.active_frames, port.longest_idle), (5, 2, Some((1, 3))));
+        assert_eq!(port.buttons, [("A".to_owned(), 2), ("R".to_owned(), 1)]);
+        
+        let duration = stats.duration.as_ref().unwrap();
+        assert_eq!((duration.frames, duration.frame_rate), (8, 60.0988));
+        assert!(!duration.from_total_frames);
+    }
+    
+    #[test]
+    fn counts_moment_only_ports() {
+        let stats = movie_stats(&movie(vec![
+            Box::new(ConsoleType::new(0x05, None)),
+            Box::new(PortController::new(1, 0x0501)),
+            Box::new(InputMoment::new(1, 0x02, 0, vec![0xFE])),
+            Box::new(InputMoment::new(1, 0x02, 70224 * 10, vec![0xFF])),
+            Box::new(InputMoment::new(1, 0x02, 70224 * 20, vec![0xFE])),
+        ]));
+        let port = &stats.ports[0];
+        assert!(port.moments_only);
+        assert_eq!((port.moments, port.active_frames, port.frames), (3, 2, 20));
+        assert_eq!(port.buttons, [("A".to_owned(), 2)]);
+        assert_eq!(stats.duration.as_ref().unwrap().frames, 20);
+        assert!(stats.pretty()[0].starts_with("Port #1 (GB Gamepad): 3 input moments, 2 with input, over 20 frames"));
+    }
+    
+    #[test]
+    fn total_frames_overrides_counted_frames() {
+        let stats = movie_stats(&movie(vec![
+            Box::new(ConsoleType::new(0x02, None)),
+            Box::new(ConsoleRegion::new(0x02)),
+            Box::new(TotalFrames::new(500)),
+        ]));
+        let duration = stats.duration.as_ref().unwrap();
+        assert_eq!((duration.frames, duration.frame_rate, duration.from_total_frames), (500, 50.0070, true));
+        assert_eq!(stats.pretty()[0], "No INPUT_CHUNK or INPUT_MOMENT packets.");
+    }
+}
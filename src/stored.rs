@@ -0,0 +1,32 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use tasd::spec::*;
+use crate::raw::{parse_packet, RawPacket};
+
+/// A packet read from a file in a different form than its fields would be written as, such as an empty string or a
+/// length written with more bytes than it needs.
+///
+/// It acts as the parsed packet (`as_any` gives the parsed packet, so it's found by `downcast_ref` like any other), but
+/// is written back exactly as it was read. Edits replace the packet, so edited packets are written normally.
+#[derive(Clone)]
+pub struct StoredPacket {
+    pub packet: Box<dyn Packet>,
+    /// The entire serialized packet, with a 2-byte key.
+    pub bytes: Vec<u8>,
+}
+impl StoredPacket {
+    pub fn new(packet: Box<dyn Packet>, raw: &RawPacket) -> Self {
+        Self { packet, bytes: raw.bytes.to_vec() }
+    }
+}
+impl Display for StoredPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.packet.fmt(f)
+    }
+}
+impl Packet for StoredPacket {
+    fn parse(key: Key, payload: &[u8]) -> Box<dyn Packet> { parse_packet(key, payload) }
+    fn raw(&self) -> Vec<u8> { self.bytes.clone() }
+    fn key(&self) -> Key { self.packet.key() }
+    fn as_any(&self) -> &dyn Any { self.packet.as_any() }
+}
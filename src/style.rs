@@ -0,0 +1,101 @@
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use crossterm::style::{Color, Stylize};
+use regex::Regex;
+use tasd::spec::Packet;
+use crate::fields::{key_name, UNKNOWN_NAME};
+use crate::timestamp;
+use crate::unknown::UnknownPacket;
+
+static ANSI_ESCAPE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1B\[[0-9;?]*[ -/]*[@-~]").unwrap());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color only when stdout is a terminal and `NO_COLOR` isn't set.
+    Auto,
+    Always,
+    Never,
+}
+impl ColorMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Auto => "auto",
+            ColorMode::Always => "always",
+            ColorMode::Never => "never",
+        }
+    }
+}
+impl FromStr for ColorMode {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            _ => Err(format!("Unknown color mode: {}", s)),
+        }
+    }
+}
+
+/// How output is formatted. Anything which formats output for the terminal is given one, so the binary decides whether
+/// it's colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    /// Whether styled output includes color escape codes.
+    pub color: bool,
+}
+impl Style {
+    pub const PLAIN: Style = Style { color: false };
+    pub const COLOR: Style = Style { color: true };
+    
+    pub fn new(mode: ColorMode) -> Self {
+        let color = match mode {
+            ColorMode::Auto => stdout_is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        };
+        
+        Self { color }
+    }
+    
+    /// Colors the text, if colors are enabled.
+    pub fn paint<D: Display>(&self, text: D, color: Color) -> String {
+        if self.color {
+            text.to_string().with(color).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+    
+    /// Formats a packet using its `Display` impl. The colors `tasd` always embeds are removed if colors are disabled.
+    ///
+    /// Timestamps are formatted here instead, showing the epoch alongside the date (and not panicking when out of range),
+    /// as are unknown packets, which don't embed any colors.
+    pub fn packet(&self, packet: &dyn Packet) -> String {
+        if let Some(epoch) = timestamp::packet_epoch(packet) {
+            let name = key_name(packet.key()).unwrap_or(UNKNOWN_NAME);
+            return format!("{} {}", self.paint(name, Color::DarkYellow), timestamp::describe(epoch));
+        }
+        if let Some(unknown) = packet.as_any().downcast_ref::<UnknownPacket>() {
+            return format!("{} {}", self.paint(unknown.name(), Color::DarkYellow), unknown.summary());
+        }
+        
+        if self.color {
+            packet.to_string()
+        } else {
+            strip_ansi(&packet.to_string())
+        }
+    }
+}
+
+pub fn stdout_is_terminal() -> bool {
+    std::io::stdout().is_terminal()
+}
+
+/// Removes any ANSI escape sequences (such as the colors embedded by each packet's `Display` impl) from the text.
+pub fn strip_ansi(text: &str) -> String {
+    ANSI_ESCAPE.replace_all(text, "").to_string()
+}
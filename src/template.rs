@@ -0,0 +1,128 @@
+use std::path::Path;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::fields::{from_fields, PacketFields, Value};
+use crate::file;
+use crate::provenance;
+use crate::raw::{packet_bytes, packet_payload, parse_packet, split_packet};
+use crate::timestamp;
+
+/// Packets which are never copied from a template, since they describe the inputs of a particular file or are set
+/// automatically when a file is created or saved.
+const SKIPPED_KEYS: [Key; 9] = [
+    KEY_CONSOLE_TYPE,
+    KEY_PORT_CONTROLLER,
+    KEY_INPUT_CHUNK,
+    KEY_INPUT_MOMENT,
+    KEY_TRANSITION,
+    KEY_LAG_FRAME_CHUNK,
+    KEY_MOVIE_TRANSITION,
+    KEY_DUMP_CREATED,
+    KEY_DUMP_LAST_MODIFIED,
+];
+
+/// Metadata packets shared by many files, such as attribution and licensing, which are copied into each of them.
+///
+/// Templates are written as TOML, with one `[[packet]]` table per packet. Each table has a `type` (the packet name, as
+/// shown when displaying packets) and the packet's fields, named as in the JSON and text formats:
+///
+/// ```toml
+/// [[packet]]
+/// type = "ATTRIBUTION"
+/// kind = 0x03 # TASD File Creator
+/// name = "Bigbass"
+///
+/// [[packet]]
+/// type = "NES_LATCH_FILTER"
+/// time = 80
+/// ```
+///
+/// Packets are held in their serialized form, so a template can be shared between threads.
+#[derive(Clone, Debug, Default)]
+pub struct Template {
+    packets: Vec<Vec<u8>>,
+}
+impl Template {
+    /// Takes every metadata packet of an existing file, apart from comments recording where that file came from.
+    pub fn from_movie(tasd: &TasdMovie) -> Self {
+        Self {
+            packets: tasd.packets.iter()
+                .filter(|packet| !SKIPPED_KEYS.contains(&packet.key()) && !provenance::is_provenance_comment(packet.as_ref()))
+                .filter_map(|packet| packet_bytes(packet.as_ref(), 2).ok()).collect(),
+        }
+    }
+    
+    /// Parses a TOML template.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let document: toml::Value = text.parse().map_err(|err: toml::de::Error| Error::Parse(err.to_string()))?;
+        let tables = match document.get("packet") {
+            Some(toml::Value::Array(tables)) => tables.as_slice(),
+            Some(_) => return Err(Error::Parse("'packet' must be a list of [[packet]] tables".to_owned())),
+            None => &[],
+        };
+        
+        let mut packets = Vec::new();
+        for (i, table) in tables.iter().enumerate() {
+            let err = |err: Error| err.context(format!("Packet #{}", i + 1));
+            let table = table.as_table().ok_or_else(|| err(Error::Parse("must be a table".to_owned())))?;
+            packets.push(from_fields(&toml_to_fields(table).map_err(|e| err(Error::Parse(e)))?).map_err(err)?.raw());
+        }
+        
+        Ok(Self { packets })
+    }
+    
+    /// Loads a template from a TOML file, or takes the metadata of a TASD file.
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("toml")) {
+            Self::from_toml(&std::fs::read_to_string(path)?)
+        } else {
+            Ok(Self::from_movie(&file::load(path)?))
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+    
+    /// Appends a copy of the template's packets to `tasd`, apart from those it already has. Returns how many were added.
+    pub fn apply(&self, tasd: &mut TasdMovie) -> usize {
+        let mut added = 0;
+        for bytes in &self.packets {
+            // Only ever created from packets which serialized successfully, so splitting can't fail
+            let raw = split_packet(bytes, 2).unwrap();
+            if tasd.packets.iter().any(|packet| packet.key() == raw.key && packet_payload(packet.as_ref()) == raw.payload()) { continue }
+            
+            tasd.packets.push(parse_packet(raw.key, raw.payload()));
+            added += 1;
+        }
+        
+        added
+    }
+}
+
+fn toml_to_fields(table: &toml::value::Table) -> std::result::Result<PacketFields, String> {
+    let name = table.get("type").and_then(|value| value.as_str()).ok_or("Missing or invalid 'type'")?;
+    let mut fields = Vec::new();
+    for (field, value) in table.iter().filter(|(field, _)| *field != "type") {
+        fields.push((field.clone(), toml_to_value(value).map_err(|err| format!("'{}' {}", field, err))?));
+    }
+    
+    Ok(PacketFields { name: name.to_owned(), fields })
+}
+
+fn toml_to_value(value: &toml::Value) -> std::result::Result<Value, String> {
+    Ok(match value {
+        toml::Value::Boolean(value) => Value::Bool(*value),
+        toml::Value::Integer(value) => Value::Int(*value as i128),
+        toml::Value::String(value) => Value::Str(value.clone()),
+        toml::Value::Array(list) => Value::List(list.iter().map(|v| v.as_integer().and_then(|v| u64::try_from(v).ok()).ok_or("must only contain whole numbers")).collect::<std::result::Result<_, _>>()?),
+        toml::Value::Table(table) => Value::Packet(Box::new(toml_to_fields(table)?)),
+        // Dates are accepted for timestamp fields, which hold epoch seconds
+        toml::Value::Datetime(value) => Value::Int(timestamp::parse(&value.to_string()).map_err(|_| format!("isn't a complete date: {}", value))? as i128),
+        toml::Value::Float(_) => return Err("must be a whole number".to_owned()),
+    })
+}
@@ -0,0 +1,338 @@
+use std::fmt::Write;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+use crate::fields::{decode_hex, encode_hex, from_fields, to_fields, PacketFields, Value};
+use crate::input::{button_layout, decode_frame, format_frame, frame_size, parse_frame, port_controllers};
+use crate::raw::{is_canonical, parse_header, parse_packet, rekey, split_packets, write_file, Header};
+
+/// Converts the contents of a TASD file into the line-oriented text format.
+///
+/// The first line is a `TASD` header, followed by one packet per line written as `TYPE field=value ...`. Each frame of
+/// an INPUT_CHUNK is written on its own line beginning with `|`, directly below the chunk. Anything after a `#` (outside
+/// of a string) is a comment. Packets which wouldn't re-serialize to the same bytes are written as `RAW data=hex:...`.
+pub fn to_text(data: &[u8]) -> Result<String> {
+    let header = parse_header(data)?;
+    let raws = split_packets(data)?;
+    let packets: Vec<Box<dyn Packet>> = raws.iter().map(|raw| parse_packet(raw.key, raw.payload())).collect();
+    
+    let controllers = port_controllers(&packets);
+    
+    let mut out = String::new();
+    writeln!(out, "TASD version={:#06X} keylen={}", header.version, header.keylen).unwrap();
+    for (raw, packet) in raws.iter().zip(&packets) {
+        if !is_canonical(packet.as_ref(), raw, header.keylen) {
+            writeln!(out, "RAW data=hex:{}  # {}", encode_hex(raw.bytes), to_fields(packet.as_ref()).name).unwrap();
+            continue;
+        }
+        
+        match packet.as_any().downcast_ref::<InputChunk>() {
+            Some(chunk) => {
+                let kind = controllers.get(&chunk.port).copied();
+                let legend = kind.and_then(button_layout).map(|layout| format!("  # {}", layout.join(" "))).unwrap_or_default();
+                writeln!(out, "INPUT_CHUNK port={}{}", chunk.port, legend).unwrap();
+                for frame in chunk.inputs.chunks(kind.map(frame_size).unwrap_or(1)) {
+                    let (text, decoded) = (format_frame(kind, frame), decode_frame(kind, frame));
+                    if text == decoded {
+                        writeln!(out, "| {}", text).unwrap();
+                    } else {
+                        writeln!(out, "| {}  # {}", text, decoded).unwrap();
+                    }
+                }
+            },
+            None => writeln!(out, "{}", format_packet(&to_fields(packet.as_ref()))).unwrap(),
+        }
+    }
+    
+    Ok(out)
+}
+
+/// Converts text created by `to_text` (or written by hand) back into the contents of a TASD file.
+pub fn from_text(text: &str) -> Result<Vec<u8>> {
+    let mut header = None;
+    let mut packets = Vec::new();
+    let mut chunk: Option<(u8, Vec<u8>)> = None;
+    
+    for (i, line) in text.lines().enumerate() {
+        let err = |err: Error| err.context(format!("Line {}", i + 1));
+        let keylen = header.map_or(2, |header: Header| header.keylen);
+        let line = strip_comment(line).trim();
+        if line.is_empty() { continue }
+        
+        if let Some(frame) = line.strip_prefix('|') {
+            match chunk.as_mut() {
+                Some((_, inputs)) => inputs.extend(parse_frame(frame).map_err(err)?),
+                None => return Err(err(Error::Parse("Input frame doesn't follow an INPUT_CHUNK".to_owned()))),
+            }
+            continue;
+        }
+        if let Some((port, inputs)) = chunk.take() {
+            packets.push(rekey(&InputChunk::new(port, inputs).raw(), 2, keylen).map_err(err)?);
+        }
+        
+        let mut parser = Parser::new(line);
+        let name = parser.word().map_err(err)?;
+        if header.is_none() {
+            if name != "TASD" { return Err(err(Error::Parse("Expected a 'TASD version=... keylen=...' header".to_owned()))) }
+            let fields = PacketFields { name, fields: parser.fields(false).map_err(err)? };
+            let version = field_int(&fields, "version").map_err(err)?;
+            let keylen = field_int(&fields, "keylen").map_err(err)?;
+            if keylen == 0 || keylen > 8 { return Err(err(Error::Parse(format!("Unsupported key width: {}", keylen)))) }
+            header = Some(Header { version, keylen });
+            continue;
+        }
+        
+        let fields = PacketFields { name, fields: parser.fields(false).map_err(err)? };
+        if fields.name == "RAW" {
+            match fields.fields.iter().find(|(name, _)| name == "data") {
+                Some((_, Value::Bytes(bytes))) => packets.push(bytes.clone()),
+                _ => return Err(err(Error::Parse("RAW requires a data=hex:... field".to_owned()))),
+            }
+        } else if fields.name == "INPUT_CHUNK" {
+            let port = field_int(&fields, "port").map_err(err)?;
+            let inputs = match fields.fields.iter().find(|(name, _)| name == "inputs") {
+                Some((_, Value::Bytes(bytes))) => bytes.clone(),
+                _ => vec![],
+            };
+            chunk = Some((port, inputs));
+        } else {
+            packets.push(rekey(&from_fields(&fields).map_err(err)?.raw(), 2, keylen).map_err(err)?);
+        }
+    }
+    
+    let header = header.ok_or_else(|| Error::Parse("Missing 'TASD' header".to_owned()))?;
+    if let Some((port, inputs)) = chunk.take() {
+        packets.push(rekey(&InputChunk::new(port, inputs).raw(), 2, header.keylen)?);
+    }
+    Ok(write_file(header, &packets))
+}
+
+fn field_int<T: TryFrom<i128>>(fields: &PacketFields, name: &str) -> Result<T> {
+    match fields.fields.iter().find(|(n, _)| n == name) {
+        Some((_, Value::Int(value))) => T::try_from(*value).map_err(|_| Error::Parse(format!("'{}' is out of range", name))),
+        _ => Err(Error::Parse(format!("{}: missing integer field '{}'", fields.name, name))),
+    }
+}
+
+fn format_packet(fields: &PacketFields) -> String {
+    let mut out = fields.name.clone();
+    for (name, value) in &fields.fields {
+        write!(out, " {}={}", name, format_value(name, value)).unwrap();
+    }
+    
+    out
+}
+
+/// Formats a field's value the way it's written in the text format.
+pub fn format_value(name: &str, value: &Value) -> String {
+    match value {
+        Value::None => "none".to_owned(),
+        Value::Bool(value) => value.to_string(),
+        // kinds are easier to cross-reference with the spec in hexadecimal
+        Value::Int(value) if (name.ends_with("kind") || name == "encoding") && *value > 0xFF => format!("{:#06X}", value),
+        Value::Int(value) if name.ends_with("kind") || name == "encoding" => format!("{:#04X}", value),
+        Value::Int(value) => value.to_string(),
+        Value::Str(value) => quote(value),
+        Value::Bytes(value) => format!("hex:{}", encode_hex(value)),
+        Value::List(value) => format!("[{}]", value.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", ")),
+        Value::Packet(fields) => format!("{{{}}}", format_packet(fields)),
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{{{:X}}}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    
+    out
+}
+
+/// Removes a trailing `# comment`, ignoring any `#` inside of a quoted string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    
+    line
+}
+
+/// Reads the `TYPE field=value ...` syntax of a single line.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self { Self { text, pos: 0 } }
+    
+    fn rest(&self) -> &'a str { &self.text[self.pos..] }
+    
+    fn skip_whitespace(&mut self) {
+        self.pos = self.text.len() - self.rest().trim_start().len();
+    }
+    
+    fn word(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let len = self.rest().find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == ':' || c == '+')).unwrap_or(self.rest().len());
+        if len == 0 { return Err(Error::Parse(format!("Expected a name or value at '{}'", self.rest()))) }
+        let word = self.rest()[..len].to_owned();
+        self.pos += len;
+        
+        Ok(word)
+    }
+    
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        if !self.rest().starts_with(c) { return Err(Error::Parse(format!("Expected '{}' at '{}'", c, self.rest()))) }
+        self.pos += c.len_utf8();
+        
+        Ok(())
+    }
+    
+    /// Reads `field=value` pairs until the end of the line, or until a closing `}` for nested packets.
+    fn fields(&mut self, nested: bool) -> Result<Vec<(String, Value)>> {
+        let mut fields = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().is_empty() || (nested && self.rest().starts_with('}')) { break }
+            
+            let name = self.word()?;
+            self.expect('=')?;
+            fields.push((name, self.value()?));
+        }
+        
+        Ok(fields)
+    }
+    
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some('"') => self.string().map(Value::Str),
+            Some('{') => {
+                self.expect('{')?;
+                let name = self.word()?;
+                let fields = self.fields(true)?;
+                self.expect('}')?;
+                Ok(Value::Packet(Box::new(PacketFields { name, fields })))
+            },
+            Some('[') => {
+                self.expect('[')?;
+                let mut list = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.rest().starts_with(']') { break }
+                    let word = self.word()?;
+                    list.push(word.parse::<u64>().map_err(|_| Error::Parse(format!("Invalid list entry: {}", word)))?);
+                    self.skip_whitespace();
+                    if self.rest().starts_with(',') { self.pos += 1 }
+                }
+                self.expect(']')?;
+                Ok(Value::List(list))
+            },
+            _ => {
+                let word = self.word()?;
+                match word.as_str() {
+                    "none" => Ok(Value::None),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => if let Some(hex) = word.strip_prefix("hex:") {
+                        decode_hex(hex).map(Value::Bytes)
+                    } else {
+                        parse_int(&word).map(Value::Int).ok_or_else(|| Error::Parse(format!("Invalid value: {}", word)))
+                    },
+                }
+            },
+        }
+    }
+    
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => { self.pos += i + 1; return Ok(out) },
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 'r')) => out.push('\r'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, 'u')) => {
+                        let code: String = chars.by_ref().map(|(_, c)| c).skip_while(|c| *c == '{').take_while(|c| *c != '}').collect();
+                        out.push(u32::from_str_radix(&code, 16).ok().and_then(char::from_u32).ok_or_else(|| Error::Parse(format!("Invalid escape: \\u{{{}}}", code)))?);
+                    },
+                    Some((_, c)) => out.push(c),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        
+        Err(Error::Parse("Unterminated string".to_owned()))
+    }
+}
+
+fn parse_int(word: &str) -> Option<i128> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word.strip_prefix('+').unwrap_or(word)),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i128>().ok()?,
+    };
+    
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::tests::fixture;
+    
+    #[test]
+    fn round_trip_is_exact() {
+        for keylen in [2, 4] {
+            let data = fixture(keylen);
+            assert_eq!(from_text(&to_text(&data).unwrap()).unwrap(), data, "keylen {}", keylen);
+        }
+    }
+    
+    #[test]
+    fn non_canonical_packets_are_written_raw() {
+        let text = to_text(&fixture(2)).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[3].starts_with("RAW data=hex:00030100"));
+        assert!(lines[4].starts_with("RAW data=hex:0003020005"));
+        assert!(lines[5].starts_with("UNKNOWN"));
+    }
+    
+    #[test]
+    fn reads_comments_and_escapes() {
+        let text = "TASD version=0x0001 keylen=2  # header\n\nCOMMENT comment=\"a # b\\n\\\"c\\\"\"  # trailing\n";
+        let data = from_text(text).unwrap();
+        let raw = split_packets(&data).unwrap();
+        assert_eq!(raw.len(), 1);
+        assert_eq!(raw[0].payload(), b"a # b\n\"c\"");
+    }
+    
+    #[test]
+    fn input_frames_follow_their_chunk() {
+        assert!(from_text("TASD version=0x0001 keylen=2\n| FF\n").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::LazyLock;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use tasd::spec::*;
+use crate::error::{Error, Result};
+
+/// Describes the formats accepted by `parse`, for use in prompts.
+pub const FORMATS_HELP: &str = "now, relative like -2d or +1h30m, epoch seconds, YYYY-MM-DD, or YYYY-MM-DD HH:MM[:SS] with an optional offset like +02:00; UTC if no offset";
+
+static RELATIVE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^([+-])((?:\d+[smhdw])+)$").unwrap());
+static RELATIVE_PART: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+)([smhdw])").unwrap());
+
+/// Parses a point in time into epoch seconds. See `FORMATS_HELP` for the accepted formats.
+pub fn parse(text: &str) -> Result<i64> {
+    let text = text.trim();
+    if text.eq_ignore_ascii_case("now") { return Ok(Utc::now().timestamp()) }
+    if let Ok(epoch) = text.parse::<i64>() { return Ok(epoch) }
+    if let Some(offset) = parse_relative(text) { return Utc::now().timestamp().checked_add(offset).ok_or_else(|| Error::Parse(format!("Out of range: {}", text))) }
+    
+    // RFC 3339 requires seconds and a 'T' separator, so the more lenient ISO-8601 forms are tried as well
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(text) { return Ok(parsed.timestamp()) }
+    let text = text.replacen('T', " ", 1);
+    let text = text.strip_suffix('Z').or_else(|| text.strip_suffix('z')).map(|text| format!("{}+00:00", text)).unwrap_or(text);
+    for format in ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%d %H:%M:%S%.f %#z", "%Y-%m-%d %H:%M%#z", "%Y-%m-%d %H:%M %#z"] {
+        if let Ok(parsed) = DateTime::parse_from_str(&text, format) { return Ok(parsed.timestamp()) }
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"] {
+        if let Ok(parsed) = NaiveDateTime::parse_from_str(&text, format) { return Ok(parsed.timestamp()) }
+    }
+    if let Ok(parsed) = NaiveDate::parse_from_str(&text, "%Y-%m-%d") { return Ok(parsed.and_hms(0, 0, 0).timestamp()) }
+    
+    Err(Error::Parse(format!("Unrecognized date/time: {}", text)))
+}
+
+/// Parses an offset from now, such as `-2d` or `+1h30m`, into seconds.
+fn parse_relative(text: &str) -> Option<i64> {
+    let captures = RELATIVE.captures(text)?;
+    
+    let mut total: i64 = 0;
+    for part in RELATIVE_PART.captures_iter(&captures[2]) {
+        let unit = match &part[2] {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 60 * 60 * 24,
+            _ => 60 * 60 * 24 * 7,
+        };
+        total = total.checked_add(part[1].parse::<i64>().ok()?.checked_mul(unit)?)?;
+    }
+    
+    Some(if &captures[1] == "-" { -total } else { total })
+}
+
+/// Formats epoch seconds as a UTC date and time, followed by the epoch itself.
+pub fn describe(epoch: i64) -> String {
+    match Utc.timestamp_opt(epoch, 0).single() {
+        Some(time) => format!("{} ({})", time.format("%Y-%m-%d %H:%M:%S UTC"), epoch),
+        None => format!("Out of range ({})", epoch),
+    }
+}
+
+/// The timestamp held by a TAS_LAST_MODIFIED, DUMP_CREATED, or DUMP_LAST_MODIFIED packet.
+pub fn packet_epoch(packet: &dyn Packet) -> Option<i64> {
+    let any = packet.as_any();
+    any.downcast_ref::<TasLastModified>().map(|packet| packet.epoch)
+        .or_else(|| any.downcast_ref::<DumpCreated>().map(|packet| packet.epoch))
+        .or_else(|| any.downcast_ref::<DumpLastModified>().map(|packet| packet.epoch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_absolute_times() {
+        assert_eq!(parse("1234567890").unwrap(), 1234567890);
+        assert_eq!(parse("2009-02-13").unwrap(), 1234483200);
+        assert_eq!(parse("2009-02-13 23:31").unwrap(), 1234567860);
+        assert_eq!(parse("2009-02-13T23:31:30Z").unwrap(), 1234567890);
+        assert_eq!(parse(" 2009-02-14 01:31:30 +02:00 ").unwrap(), 1234567890);
+        assert_eq!(parse("2009-02-13 23:31:30.5").unwrap(), 1234567890);
+    }
+    
+    #[test]
+    fn parses_relative_offsets() {
+        assert_eq!(parse_relative("+90s"), Some(90));
+        assert_eq!(parse_relative("-1h30m"), Some(-5400));
+        assert_eq!(parse_relative("+2w1d"), Some(15 * 24 * 60 * 60));
+        assert_eq!(parse_relative("1h"), None);
+        assert_eq!(parse_relative("+1y"), None);
+        assert_eq!(parse_relative("+99999999999999999w"), None);
+        
+        let now = Utc::now().timestamp();
+        assert!((parse("-2d").unwrap() - (now - 2 * 24 * 60 * 60)).abs() <= 1);
+    }
+    
+    #[test]
+    fn rejects_unrecognized_times() {
+        assert_eq!(parse("yesterday").unwrap_err().to_string(), "Invalid input: Unrecognized date/time: yesterday");
+        assert!(parse("2009-13-01").is_err());
+    }
+    
+    #[test]
+    fn describes_epochs_in_utc() {
+        assert_eq!(describe(1234567890), "2009-02-13 23:31:30 UTC (1234567890)");
+        assert_eq!(describe(i64::MAX), format!("Out of range ({})", i64::MAX));
+    }
+}
@@ -0,0 +1,98 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use tasd::spec::*;
+use crate::fields::{key_label, key_name};
+use crate::raw::RawPacket;
+
+/// Number of payload bytes shown when a packet is displayed on a single line.
+const PREVIEW_LEN: usize = 16;
+
+/// A packet with a key the spec doesn't define (such as one from a newer version), or whose payload couldn't be read.
+///
+/// The packet is kept exactly as it was read, including the encoding of its length, so saving a file never changes or
+/// drops it. Packets whose key doesn't fit in 2 bytes keep their original key, and can only be saved with a key width
+/// it fits in.
+#[derive(Clone, Debug)]
+pub struct UnknownPacket {
+    /// The packet's key, or `OVERSIZED_KEY` if it doesn't fit in 2 bytes.
+    pub key: Key,
+    /// The entire serialized packet, with a `keylen`-byte key.
+    pub bytes: Vec<u8>,
+    payload_start: usize,
+    keylen: u8,
+}
+impl UnknownPacket {
+    /// Creates a packet from its payload, using the shortest length encoding.
+    pub fn new(key: Key, payload: &[u8]) -> Self {
+        let len = (payload.len() as u64).to_be_bytes();
+        let exp = len.iter().position(|byte| *byte != 0).map(|i| 8 - i).unwrap_or(1);
+        
+        let mut bytes = key.to_vec();
+        bytes.push(exp as u8);
+        bytes.extend_from_slice(&len[(8 - exp)..]);
+        bytes.extend_from_slice(payload);
+        
+        Self { key, bytes, payload_start: 3 + exp, keylen: 2 }
+    }
+    
+    /// Keeps a packet exactly as it was read from a file with the given key width.
+    pub fn from_raw(raw: &RawPacket, keylen: u8) -> Self {
+        Self { key: raw.key, bytes: raw.bytes.to_vec(), payload_start: raw.payload_start, keylen }
+    }
+    
+    /// Width of the key in `bytes`. Only differs from 2 if the key doesn't fit in 2 bytes.
+    pub fn keylen(&self) -> u8 {
+        self.keylen
+    }
+    
+    /// The key exactly as it was read.
+    pub fn key_bytes(&self) -> &[u8] {
+        &self.bytes[..self.keylen as usize]
+    }
+    
+    pub fn payload(&self) -> &[u8] {
+        &self.bytes[self.payload_start..]
+    }
+    
+    /// Whether the spec defines this key, meaning the packet is only here because its payload couldn't be read.
+    pub fn is_known_key(&self) -> bool {
+        key_name(self.key).is_some()
+    }
+    
+    /// Name of the packet's type, e.g. `UNKNOWN 0xABCD`, or `GAME_TITLE` when only the payload couldn't be read.
+    pub fn name(&self) -> String {
+        key_label(self.key_bytes())
+    }
+    
+    /// Everything shown after the name when the packet is displayed, e.g. `(3 bytes of payload): AA BB CC`.
+    pub fn summary(&self) -> String {
+        let payload = self.payload();
+        let reason = if self.is_known_key() { "unreadable payload" } else { "payload" };
+        let mut text = format!("({} bytes of {})", payload.len(), reason);
+        if !payload.is_empty() {
+            let preview: Vec<String> = payload.iter().take(PREVIEW_LEN).map(|byte| format!("{:02X}", byte)).collect();
+            text.push_str(&format!(": {}{}", preview.join(" "), if payload.len() > PREVIEW_LEN { " ..." } else { "" }));
+        }
+        text
+    }
+}
+impl Display for UnknownPacket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name(), self.summary())
+    }
+}
+impl Packet for UnknownPacket {
+    fn parse(key: Key, payload: &[u8]) -> Box<dyn Packet> { Box::new(Self::new(key, payload)) }
+    fn raw(&self) -> Vec<u8> { self.bytes.clone() }
+    fn key(&self) -> Key { self.key }
+    fn as_any(&self) -> &dyn Any { self }
+}
+
+pub fn is_unknown(packet: &dyn Packet) -> bool {
+    packet.as_any().is::<UnknownPacket>()
+}
+
+/// Indexes of every unknown or unreadable packet in the movie.
+pub fn unknown_packets(tasd: &TasdMovie) -> Vec<usize> {
+    tasd.packets.iter().enumerate().filter(|(_, packet)| is_unknown(packet.as_ref())).map(|(i, _)| i).collect()
+}
@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use tasd::spec::*;
+use crate::fields::encode_hex;
+use crate::input::port_controllers;
+use crate::unknown::UnknownPacket;
+
+/// A problem found in a file.
+pub struct Issue {
+    /// Index of the packet the problem is with, if it's with a single packet.
+    pub index: Option<usize>,
+    pub message: String,
+}
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "[{}]: {}", index, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Checks a movie for anything which may stop it from being read or replayed correctly: a newer version of the spec,
+/// unknown or unreadable packets, and missing or conflicting console and controller information.
+pub fn validate(tasd: &TasdMovie) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut issue = |index: Option<usize>, message: String| issues.push(Issue { index, message });
+    
+    let latest = u16::from_be_bytes(LATEST_VERSION);
+    if tasd.version > latest {
+        issue(None, format!("File uses TASD version {:#06X}, but only versions up to {:#06X} are supported.", tasd.version, latest));
+    }
+    
+    for (i, packet) in tasd.packets.iter().enumerate() {
+        if let Some(unknown) = packet.as_any().downcast_ref::<UnknownPacket>() {
+            let len = unknown.payload().len();
+            if unknown.is_known_key() {
+                issue(Some(i), format!("{} payload couldn't be read ({} bytes). It's kept as-is, but can't be edited.", unknown.name(), len));
+            } else {
+                issue(Some(i), format!("Unknown packet key 0x{} ({} byte payload). It's kept as-is when saving.", encode_hex(unknown.key_bytes()), len));
+            }
+        }
+    }
+    
+    let console_types: HashSet<u8> = tasd.packets.iter().filter_map(|packet| packet.as_any().downcast_ref::<ConsoleType>()).map(|packet| packet.kind).collect();
+    match console_types.len() {
+        0 => issue(None, "No CONSOLE_TYPE packet, so the console this is for is unknown.".to_owned()),
+        1 => (),
+        _ => issue(None, "CONSOLE_TYPE packets name more than one console.".to_owned()),
+    }
+    
+    let controllers = port_controllers(&tasd.packets);
+    let mut ports: Vec<u8> = tasd.packets.iter().filter_map(|packet| {
+        let any = packet.as_any();
+        any.downcast_ref::<InputChunk>().map(|chunk| chunk.port).or_else(|| any.downcast_ref::<InputMoment>().map(|moment| moment.port))
+    }).filter(|port| !controllers.contains_key(port)).collect();
+    ports.sort();
+    ports.dedup();
+    for port in ports {
+        issue(None, format!("Port #{} has inputs, but no PORT_CONTROLLER packet saying what's plugged into it.", port));
+    }
+    
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    
+    fn movie(version: u16, packets: Vec<Box<dyn Packet>>) -> TasdMovie {
+        TasdMovie { version, keylen: 2, packets, source_path: PathBuf::from("movie.tasd") }
+    }
+    
+    fn messages(tasd: &TasdMovie) -> Vec<String> {
+        validate(tasd).iter().map(|issue| issue.to_string()).collect()
+    }
+    
+    #[test]
+    fn complete_files_have_no_issues() {
+        let tasd = movie(1, vec![
+            Box::new(ConsoleType::new(0x01, None)),
+            Box::new(PortController::new(1, 0x0101)),
+            Box::new(InputChunk::new(1, vec![0xFF])),
+            Box::new(InputMoment::new(1, 0x01, 5, vec![0x7F])),
+        ]);
+        assert!(validate(&tasd).is_empty());
+    }
+    
+    #[test]
+    fn reports_newer_versions_and_console_problems() {
+        let latest = u16::from_be_bytes(LATEST_VERSION);
+        assert_eq!(messages(&movie(latest + 1, vec![Box::new(ConsoleType::new(0x01, None))])), [
+            format!("File uses TASD version {:#06X}, but only versions up to {:#06X} are supported.", latest + 1, latest),
+        ]);
+        assert_eq!(messages(&movie(1, vec![])), ["No CONSOLE_TYPE packet, so the console this is for is unknown."]);
+        assert_eq!(messages(&movie(1, vec![Box::new(ConsoleType::new(0x01, None)), Box::new(ConsoleType::new(0x02, None))])), ["CONSOLE_TYPE packets name more than one console."]);
+    }
+    
+    #[test]
+    fn reports_unknown_and_unreadable_packets_by_index() {
+        let tasd = movie(1, vec![
+            Box::new(ConsoleType::new(0x01, None)),
+            Box::new(UnknownPacket::new([0x77, 0x77], &[0xAA, 0xBB])),
+            Box::new(UnknownPacket::new(KEY_GAME_TITLE, &[0x01])),
+        ]);
+        let issues = validate(&tasd);
+        assert_eq!(issues.iter().map(|issue| issue.index).collect::<Vec<Option<usize>>>(), [Some(1), Some(2)]);
+        assert_eq!(issues[0].to_string(), "[1]: Unknown packet key 0x7777 (2 byte payload). It's kept as-is when saving.");
+        assert!(issues[1].message.starts_with("GAME_TITLE payload couldn't be read (1 bytes)."));
+    }
+    
+    #[test]
+    fn reports_each_port_without_a_controller_once() {
+        let tasd = movie(1, vec![
+            Box::new(ConsoleType::new(0x01, None)),
+            Box::new(PortController::new(1, 0x0101)),
+            Box::new(InputChunk::new(3, vec![0xFF])),
+            Box::new(InputChunk::new(2, vec![0xFF])),
+            Box::new(InputMoment::new(3, 0x01, 5, vec![0xFF])),
+        ]);
+        assert_eq!(messages(&tasd), [
+            "Port #2 has inputs, but no PORT_CONTROLLER packet saying what's plugged into it.",
+            "Port #3 has inputs, but no PORT_CONTROLLER packet saying what's plugged into it.",
+        ]);
+    }
+}